@@ -0,0 +1,72 @@
+pub mod engine;
+pub mod referee;
+
+use std::{env, process, time::Duration};
+
+use engine::rules::{Rules, LEAGUE_NAMES};
+use referee::{
+    arena::{run_arena, ArenaSettings},
+    MatchSettings,
+};
+
+const USAGE: &str = "usage: arena <bot 1 command> <bot 2 command> [-n <games>] [-t <threads>] [--seed <n>] [--timeout-ms <n>] [--league <name>]";
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    eprintln!("leagues: {}", LEAGUE_NAMES.join(", "));
+    process::exit(2);
+}
+
+fn next_number<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>) -> T {
+    args.next()
+        .and_then(|x| x.parse().ok())
+        .unwrap_or_else(|| exit_with_usage())
+}
+
+fn main() {
+    let mut commands = Vec::new();
+    let mut games = 100;
+    let mut threads = 8;
+    let mut seed: u64 = rand::random();
+    let mut match_settings = MatchSettings::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" => games = next_number(&mut args),
+            "-t" => threads = next_number(&mut args),
+            "--seed" => seed = next_number(&mut args),
+            "--timeout-ms" => {
+                match_settings.turn_timeout = Duration::from_millis(next_number(&mut args))
+            }
+            "--league" => {
+                match_settings.rules = args
+                    .next()
+                    .and_then(|x| Rules::by_name(&x))
+                    .unwrap_or_else(|| exit_with_usage())
+            }
+            _ => commands.push(arg),
+        }
+    }
+    if commands.len() != 2 {
+        exit_with_usage();
+    }
+
+    println!("seed: {}", seed);
+    let settings = ArenaSettings {
+        commands: [commands[0].clone(), commands[1].clone()],
+        games,
+        threads,
+        seed,
+        match_settings,
+    };
+    let stats = run_arena(settings, |game_id, result, seat, _| {
+        println!(
+            "game {} (bot 1 as player {}): {}",
+            game_id,
+            seat + 1,
+            result
+        );
+    });
+    println!("{}", stats);
+}
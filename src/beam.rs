@@ -1,32 +1,12 @@
 pub mod engine;
-use engine::{game::search_next_action, game_loop::*};
+use engine::{game_loop::*, rules::Rules, strategies::BeamSearch};
 use std::env;
 
-pub struct BeamSearch {}
-
-impl BeamSearch {
-    pub fn new() -> BeamSearch {
-        BeamSearch {}
-    }
-}
-
-impl Strategy for BeamSearch {
-    fn on_start(&mut self, board: &engine::board::Board) {}
-
-    fn get_next(
-        &mut self,
-        game: &engine::game::Game,
-        board: &engine::board::Board,
-        time_limit: u128,
-    ) -> engine::actions::Action {
-        let (d, action) = search_next_action(game, board, 5, 100, time_limit);
-        eprintln!("depth is {}", d);
-        action
-    }
-}
-
+/// Plays the full game by default, an optional argument picks another league.
 fn main() {
-    //let args: Vec<String> = env::args().collect();
-    //println!("{:?}", args);
-    play_game(BeamSearch::new(), GameSettings::new());
+    let rules = env::args()
+        .nth(1)
+        .and_then(|x| Rules::by_name(&x))
+        .unwrap_or_default();
+    play_game(BeamSearch::new(), GameSettings::new().with_rules(rules));
 }
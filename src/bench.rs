@@ -1,9 +1,6 @@
 #![feature(test)]
 
-use std::{
-    collections::HashMap,
-    time::{Duration, Instant},
-};
+use std::time::{Duration, Instant};
 
 use itertools::Itertools;
 use rand::prelude::SliceRandom;
@@ -88,13 +85,12 @@ pub fn main() {
     let mut sim = Simulation::new(&board, game);
 
     let d = Instant::now();
-    let mut cache = HashMap::new();
 
     let _for_lookup = Game::parse_from_strings(vec![
         "1", "20", "4 0", "4 0 0", "4", "19 1 0 0", "24 1 0 0", "28 1 1 0", "33 1 1 0",
     ]);
     for _ in 0..10000 {
-        sim.simulate_current(&mut cache);
+        sim.simulate_current();
     }
 
     println!("{} ms.", Duration::as_millis(&d.elapsed()),);
@@ -1,5 +1,5 @@
 use super::{
-    board::{Board, Cell},
+    board::{index_to_coord, Board, Cell},
     common::ParseError,
     game::Game,
     tree::Tree,
@@ -16,8 +16,53 @@ pub enum Action {
     SEED(u8, u8),
 }
 
+/// The reason an action can't be played, as the referee would report it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IllegalAction {
+    AlreadyAsleep,
+    NotAllowed,
+    UnusableCell(u8),
+    NoTree(u8),
+    NotOwner(u8),
+    TreeDormant(u8),
+    TreeTooSmall(u8),
+    TreeFullyGrown(u8),
+    TargetOccupied(u8),
+    OutOfSeedRange { from: u8, to: u8 },
+    NotEnoughSun { cost: u8, available: u16 },
+}
+
+impl Display for IllegalAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IllegalAction::AlreadyAsleep => write!(f, "player is asleep until the next day"),
+            IllegalAction::NotAllowed => write!(f, "action is not allowed in this league"),
+            IllegalAction::UnusableCell(x) => write!(f, "cell {} is not usable", x),
+            IllegalAction::NoTree(x) => write!(f, "there is no tree on cell {}", x),
+            IllegalAction::NotOwner(x) => write!(f, "tree on cell {} is not yours", x),
+            IllegalAction::TreeDormant(x) => write!(f, "tree on cell {} is dormant", x),
+            IllegalAction::TreeTooSmall(x) => write!(f, "tree on cell {} is too small", x),
+            IllegalAction::TreeFullyGrown(x) => {
+                write!(f, "tree on cell {} is already fully grown", x)
+            }
+            IllegalAction::TargetOccupied(x) => write!(f, "cell {} is already occupied", x),
+            IllegalAction::OutOfSeedRange { from, to } => {
+                write!(f, "cell {} is too far from the tree on cell {}", to, from)
+            }
+            IllegalAction::NotEnoughSun { cost, available } => write!(
+                f,
+                "not enough sun points: {} needed, {} available",
+                cost, available
+            ),
+        }
+    }
+}
+
 impl Action {
     pub fn find_next_actions(game: &Game, board: &Board, is_player: bool) -> Vec<Action> {
+        if game.is_waiting(is_player) {
+            return vec![Action::WAIT];
+        }
         vec![
             vec![Action::WAIT],
             Self::find_next_complete_actions(game, board, is_player),
@@ -41,6 +86,9 @@ impl Action {
     }
 
     pub fn find_next_grow_actions(game: &Game, _board: &Board, is_player: bool) -> Vec<Action> {
+        if !game.rules().grow_enabled {
+            return Vec::new();
+        }
         game.trees()
             .iter_trees_for(is_player)
             .filter(|t| t.size() < 3 && !t.is_dormant())
@@ -49,12 +97,7 @@ impl Action {
     }
 
     pub fn get_grow_cost(game: &Game, size: u8, is_player: bool) -> u8 {
-        match size {
-            0 => 1 + game.trees().get_amount_of_size(1, is_player),
-            1 => 3 + game.trees().get_amount_of_size(2, is_player),
-            2 => 7 + game.trees().get_amount_of_size(3, is_player),
-            _ => panic!("Can't grow a tree of size {}", size),
-        }
+        game.rules().grow_cost(size) + game.trees().get_amount_of_size(size + 1, is_player)
     }
 
     pub fn get_action_cost(game: &Game, action: Action, is_player: bool) -> u8 {
@@ -65,14 +108,80 @@ impl Action {
                 let tree = game.trees().get(tree_index);
                 Self::get_grow_cost(game, tree.size(), is_player)
             }
-            Action::COMPLETE(_) => 4,
+            Action::COMPLETE(_) => game.rules().complete_cost,
+        }
+    }
+
+    /// Checks the action the same way the referee does, in the same order, so
+    /// the reported reason matches the one in the game summary.
+    pub fn validate(
+        &self,
+        game: &Game,
+        board: &Board,
+        is_player: bool,
+    ) -> Result<(), IllegalAction> {
+        let rules = game.rules();
+        let allowed = match self {
+            Action::WAIT => return Ok(()),
+            Action::COMPLETE(_) => true,
+            Action::GROW(_) => rules.grow_enabled,
+            Action::SEED(_, _) => rules.seeds_enabled,
+        };
+        if game.is_waiting(is_player) {
+            return Err(IllegalAction::AlreadyAsleep);
+        }
+        if !allowed {
+            return Err(IllegalAction::NotAllowed);
+        }
+
+        let index = match *self {
+            Action::COMPLETE(x) | Action::GROW(x) | Action::SEED(x, _) => x,
+            Action::WAIT => unreachable!(),
+        };
+        let cell_count = board.cells().count();
+        if index as usize >= cell_count {
+            return Err(IllegalAction::UnusableCell(index));
+        }
+        if !game.trees().has_at(index) {
+            return Err(IllegalAction::NoTree(index));
+        }
+        let tree = game.trees().get(index);
+        if tree.is_mine() != is_player {
+            return Err(IllegalAction::NotOwner(index));
+        }
+        if tree.is_dormant() {
+            return Err(IllegalAction::TreeDormant(index));
+        }
+
+        match *self {
+            Action::COMPLETE(x) if tree.size() < 3 => return Err(IllegalAction::TreeTooSmall(x)),
+            Action::GROW(x) if tree.size() >= 3 => return Err(IllegalAction::TreeFullyGrown(x)),
+            Action::SEED(from, to) => {
+                if to as usize >= cell_count || board.get_richness(to) == 0 {
+                    return Err(IllegalAction::UnusableCell(to));
+                }
+                if index_to_coord(from).distance_to(index_to_coord(to)) > tree.size() {
+                    return Err(IllegalAction::OutOfSeedRange { from, to });
+                }
+                if game.trees().has_at(to) {
+                    return Err(IllegalAction::TargetOccupied(to));
+                }
+            }
+            _ => {}
+        }
+
+        let cost = Self::get_action_cost(game, *self, is_player);
+        let available = game.get_sun_points(is_player);
+        if cost as u16 > available {
+            return Err(IllegalAction::NotEnoughSun { cost, available });
         }
+        Ok(())
     }
 
     fn get_seedable_neighbors<'a>(
         game: &'a Game,
         board: &'a Board,
-        tree: &Tree,
+        tree: Tree,
     ) -> impl Iterator<Item = &'a Cell> {
         board
             .get_neighbors_from(tree.index(), tree.size())
@@ -80,6 +189,9 @@ impl Action {
     }
 
     pub fn find_next_seed_actions(game: &Game, board: &Board, is_player: bool) -> Vec<Action> {
+        if !game.rules().seeds_enabled {
+            return Vec::new();
+        }
         let trees = game.trees();
         return trees
             .iter_trees_for(is_player)
@@ -158,6 +270,74 @@ mod tests {
         assert_eq!(Ok(Action::SEED(32, 1)), result);
     }
 
+    #[test]
+    fn validate_agrees_with_next_actions() {
+        let trees = TreeCollection::from_strings(vec![
+            "5 2 0 0", "6 1 0 0", "13 0 0 0", "15 2 0 0", "21 3 1 0", "27 1 0 1", "30 1 1 1",
+            "36 2 1 0", "9 0 1 0", "3 1 1 0",
+        ]);
+        let board = Board::default_with_inactive(vec![25, 23, 32, 34].into_iter());
+        let game = Game::new(trees, 20, 9, 10, 0, 0, 7, false);
+        let moves = Action::find_next_actions(&game, &board, true);
+
+        let mut candidates = vec![Action::WAIT];
+        for from in 0..37 {
+            candidates.push(Action::COMPLETE(from));
+            candidates.push(Action::GROW(from));
+            candidates.extend((0..37).map(|to| Action::SEED(from, to)));
+        }
+        for action in candidates {
+            assert_eq!(
+                action.validate(&game, &board, true).is_ok(),
+                moves.contains(&action),
+                "{}",
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn validate_explains_illegal_actions() {
+        let trees = TreeCollection::from_strings(vec![
+            "0 1 1 0", "1 3 1 1", "2 0 0 0", "21 3 1 0", "22 0 1 0", "4 2 1 0",
+        ]);
+        let board = Board::default_with_inactive(vec![3].into_iter());
+        let game = Game::new(trees, 20, 3, 10, 0, 0, 5, false);
+
+        let check = |action: &str| {
+            action
+                .parse::<Action>()
+                .unwrap()
+                .validate(&game, &board, true)
+        };
+        assert_eq!(check("GROW 7"), Err(IllegalAction::NoTree(7)));
+        assert_eq!(check("GROW 2"), Err(IllegalAction::NotOwner(2)));
+        assert_eq!(check("COMPLETE 1"), Err(IllegalAction::TreeDormant(1)));
+        assert_eq!(check("COMPLETE 0"), Err(IllegalAction::TreeTooSmall(0)));
+        assert_eq!(check("GROW 21"), Err(IllegalAction::TreeFullyGrown(21)));
+        assert_eq!(check("SEED 4 3"), Err(IllegalAction::UnusableCell(3)));
+        assert_eq!(check("SEED 0 2"), Err(IllegalAction::TargetOccupied(2)));
+        assert_eq!(
+            check("SEED 0 8"),
+            Err(IllegalAction::OutOfSeedRange { from: 0, to: 8 })
+        );
+        assert_eq!(
+            check("COMPLETE 21"),
+            Err(IllegalAction::NotEnoughSun {
+                cost: 4,
+                available: 3
+            })
+        );
+        assert_eq!(check("SEED 21 9"), Ok(()));
+        assert_eq!(check("WAIT"), Ok(()));
+
+        let asleep = game.apply_actions(&board, Action::WAIT, Action::GROW(2));
+        assert_eq!(
+            Action::GROW(0).validate(&asleep, &board, true),
+            Err(IllegalAction::AlreadyAsleep)
+        );
+    }
+
     #[test]
     fn available_actions_seed_start_game() {
         let trees =
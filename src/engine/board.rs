@@ -1,4 +1,4 @@
-use std::{collections::HashMap, iter::FromIterator, str::FromStr, u8, usize};
+use std::{collections::HashMap, fmt::Display, iter::FromIterator, str::FromStr, u8, usize};
 
 use itertools::Itertools;
 
@@ -171,6 +171,7 @@ pub struct Board {
     neighbors_1: Vec<Vec<u8>>,
     neighbors_2: Vec<Vec<u8>>,
     neighbors_3: Vec<Vec<u8>>,
+    shadows: Vec<[[u64; 4]; 6]>,
 }
 
 impl Board {
@@ -185,9 +186,39 @@ impl Board {
             neighbors_1: Self::build_neighbors(1),
             neighbors_2: Self::build_neighbors(2),
             neighbors_3: Self::build_neighbors(3),
+            shadows: Self::build_shadows(),
         }
     }
 
+    fn build_shadows() -> Vec<[[u64; 4]; 6]> {
+        let center = CubeCoord::new(0, 0, 0);
+        (0..37u8)
+            .map(|index| {
+                let start = index_to_coord(index);
+                let mut result = [[0u64; 4]; 6];
+                for orientation in 0..6u8 {
+                    for size in 1..4u8 {
+                        let c = start.at_distance(orientation, size);
+                        let line = result[orientation as usize][size as usize - 1];
+                        result[orientation as usize][size as usize] = if c.distance_to(center) <= 3
+                        {
+                            line | 1 << coord_to_index(c)
+                        } else {
+                            line
+                        };
+                    }
+                }
+                result
+            })
+            .collect()
+    }
+
+    /// Cells shaded by a tree of the given size standing on `index` when the sun
+    /// points to `orientation`, as a bit mask.
+    pub fn shadow_line(&self, index: u8, orientation: u8, size: u8) -> u64 {
+        self.shadows[index as usize][orientation as usize][size as usize]
+    }
+
     fn build_neighbors(d: u8) -> Vec<Vec<u8>> {
         let mut result = Vec::new();
         for index in 0..37 {
@@ -247,6 +278,10 @@ impl Board {
         self.cells[i as usize].richness
     }
 
+    pub fn cells(&self) -> impl Iterator<Item = &Cell> {
+        self.cells.iter()
+    }
+
     pub fn default_with_inactive(inactive_cells: impl Iterator<Item = u8>) -> Self {
         let inactive_vec: Vec<_> = inactive_cells.collect();
         let default_matrix = vec![
@@ -349,6 +384,27 @@ impl Cell {
     }
 }
 
+impl Display for Cell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn from_edge(edge: Edge) -> i8 {
+            edge.map(|x| x as i8).unwrap_or(-1)
+        }
+
+        write!(
+            f,
+            "{} {} {} {} {} {} {} {}",
+            self.index,
+            self.richness,
+            from_edge(self.neig_0),
+            from_edge(self.neig_1),
+            from_edge(self.neig_2),
+            from_edge(self.neig_3),
+            from_edge(self.neig_4),
+            from_edge(self.neig_5)
+        )
+    }
+}
+
 impl FromStr for Cell {
     type Err = ParseError;
 
@@ -403,12 +459,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_can_print() {
+        let cell = "19 1 -1 -1 20 7 36 -1".parse::<Cell>().unwrap();
+        assert_eq!(cell.to_string(), "19 1 -1 -1 20 7 36 -1");
+    }
+
     #[test]
     fn default_exists() {
         let board = Board::default();
         assert_eq!(board.cells.len(), 37);
     }
 
+    #[test]
+    fn test_shadow_line_matches_get_line() {
+        let board = Board::default();
+        for index in 0..37u8 {
+            for orientation in 0..6u8 {
+                for size in 0..4u8 {
+                    let expected = board
+                        .get_line(index_to_coord(index), size, orientation)
+                        .fold(0u64, |mask, c| mask | 1 << c.index);
+                    assert_eq!(board.shadow_line(index, orientation, size), expected);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_coordinates_at_distance() {
         let start = CubeCoord::new(0, 0, 0);
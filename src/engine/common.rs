@@ -33,9 +33,30 @@ where
     return result.into_iter().choose(&mut rand);
 }
 
+/// The `n` greatest items, greatest first, ties in the order of `items`.
+/// Unlike sorting, it does not panic when `compare` is not a total order.
+pub fn top_n<T, F>(mut items: Vec<T>, n: usize, mut compare: F) -> Vec<T>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut result = Vec::with_capacity(n.min(items.len()));
+    while result.len() < n && !items.is_empty() {
+        let mut best = 0;
+        for i in 1..items.len() {
+            if compare(&items[i], &items[best]) == Ordering::Greater {
+                best = i;
+            }
+        }
+        result.push(items.remove(best));
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
-    use super::super::common::random_max;
+    use std::cmp::Ordering;
+
+    use super::super::common::{random_max, top_n};
 
     #[test]
     fn it_works() {
@@ -46,4 +67,22 @@ mod tests {
         ];
         println!("{:?}", x);
     }
+
+    #[test]
+    fn test_top_n() {
+        let items = vec![(0, 10), (1, 20), (2, 5), (3, 20)];
+        assert_eq!(
+            top_n(items.clone(), 3, |x, y| x.1.cmp(&y.1)),
+            vec![(1, 20), (3, 20), (0, 10)]
+        );
+        assert_eq!(top_n(items, 10, |x, y| x.1.cmp(&y.1)).len(), 4);
+
+        // Rock, paper, scissors has no order at all.
+        let beats = |x: &u8, y: &u8| match (x + 3 - y) % 3 {
+            0 => Ordering::Equal,
+            1 => Ordering::Greater,
+            _ => Ordering::Less,
+        };
+        assert_eq!(top_n(vec![0, 1, 2], 2, beats).len(), 2);
+    }
 }
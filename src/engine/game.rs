@@ -1,8 +1,9 @@
 use core::{panic, time};
 use std::{
     cmp::{Ordering, Reverse},
-    collections::{vec_deque, BinaryHeap, HashMap},
+    collections::{vec_deque, BinaryHeap, HashMap, HashSet},
     fmt::{Debug, Display},
+    hash::{Hash, Hasher},
     time::Instant,
     u32, u8, usize,
 };
@@ -11,12 +12,19 @@ use itertools::{Iterate, Itertools};
 
 use super::{
     actions::Action,
-    board::{index_to_coord, Board},
+    board::Board,
+    common::top_n,
+    opponent::{Aggregation, OpponentModel, WaitingOpponent},
     parse::Next,
-    simulation::Simulation,
+    rules::Rules,
+    simulation::{Score, Simulation},
     tree::{Tree, TreeCollection},
+    zobrist,
 };
 
+/// The full game lasts 24 days: 0-23.
+pub const MAX_DAYS: u8 = Rules::bronze().max_days;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Shadow {
     index: u8,
@@ -24,7 +32,7 @@ pub struct Shadow {
     caused_by_player: bool,
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Game {
     //board: &'a board::Board,
     trees: TreeCollection,
@@ -36,9 +44,11 @@ pub struct Game {
     my_points: u16,
     enemy_points: u16,
 
+    player_waiting: bool,
     opponent_waiting: bool,
 
     pub day: u8,
+    rules: Rules,
 }
 /*
 impl Debug for Game {
@@ -47,11 +57,17 @@ impl Debug for Game {
     }
 }*/
 
+impl Hash for Game {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.key());
+    }
+}
+
 impl<'a> Display for Game {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "(day: {}, trees: {:?}, player_sp: {}, enemy_sun_points: {}, nutrients: {}, player_points: {}, enemy_points: {}, op_waiting: {})",
+            "(day: {}, trees: {:?}, player_sp: {}, enemy_sun_points: {}, nutrients: {}, player_points: {}, enemy_points: {}, player_waiting: {}, op_waiting: {})",
             self.day,
             self.trees,
             self.get_sun_points(true),
@@ -59,6 +75,7 @@ impl<'a> Display for Game {
             self.nutrients,
             self.my_points,
             self.enemy_points,
+            self.player_waiting,
             self.opponent_waiting
         )
     }
@@ -85,7 +102,67 @@ impl Game {
             day,
             enemy_points: enemy_score,
             my_points: my_score,
+            player_waiting: false,
             opponent_waiting: opponent_waiting,
+            rules: Rules::default(),
+        }
+    }
+
+    pub fn with_rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.day >= self.rules.max_days
+    }
+
+    /// Zobrist key of the state. The trees part is kept up to date by the
+    /// collection, the few numbers on top of it are mixed in here. The rules
+    /// are not part of the key.
+    pub fn key(&self) -> u64 {
+        let waiting = self.player_waiting as u16 | (self.opponent_waiting as u16) << 1;
+        self.trees.key()
+            ^ zobrist::value(0, self.my_sun_points)
+            ^ zobrist::value(1, self.enemy_sun_points)
+            ^ zobrist::value(2, self.my_points)
+            ^ zobrist::value(3, self.enemy_points)
+            ^ zobrist::value(4, self.nutrients)
+            ^ zobrist::value(5, self.day as u16)
+            ^ zobrist::value(6, waiting)
+    }
+
+    /// A player who waited is asleep until the next day and can't act anymore.
+    pub fn is_waiting(&self, is_player: bool) -> bool {
+        if is_player {
+            self.player_waiting
+        } else {
+            self.opponent_waiting
+        }
+    }
+
+    /// The same game seen from the enemy's side: trees, sun, points and
+    /// waiting flags of both players are swapped.
+    pub fn swap_players(&self) -> Game {
+        Self {
+            trees: self
+                .trees
+                .iter()
+                .map(|t| Tree::new(t.index(), t.size(), !t.is_mine(), t.is_dormant()))
+                .collect(),
+            nutrients: self.nutrients,
+            my_sun_points: self.enemy_sun_points,
+            enemy_sun_points: self.my_sun_points,
+            my_points: self.enemy_points,
+            enemy_points: self.my_points,
+            player_waiting: self.opponent_waiting,
+            opponent_waiting: self.player_waiting,
+            day: self.day,
+            rules: self.rules,
         }
     }
 
@@ -103,10 +180,12 @@ impl Game {
 
     pub fn get_harvest_cost_by_size(&self, size: u8, is_player: bool) -> i32 {
         match size {
-            3 => 4,
-            2 => 7 + 4,
-            1 => 3 + 7 + 4,
-            0 => 1 + 3 + 7 + 4,
+            0..=3 => {
+                (size..3)
+                    .map(|s| self.rules.grow_cost(s) as i32)
+                    .sum::<i32>()
+                    + self.rules.complete_cost as i32
+            }
             _ => panic!("{} is invalid size", size),
         }
     }
@@ -129,26 +208,12 @@ impl Game {
             trees: TreeCollection::new(Vec::new()),
             enemy_points: 0,
             my_points: 0,
+            player_waiting: false,
             opponent_waiting: false,
+            rules: Rules::default(),
         }
     }
 
-    fn find_shadows_by<'a, 'b>(
-        &self,
-        board: &'a Board,
-        tree_index: u8,
-        sun_orientation: u8,
-    ) -> impl Iterator<Item = u8> + 'b
-    where
-        'a: 'b,
-    {
-        let tree = self.trees().get(tree_index);
-        board
-            .get_line(index_to_coord(tree.index()), tree.size(), sun_orientation)
-            .map(|x| x.index)
-            .into_iter()
-    }
-
     pub fn find_shadowed_area(&self, board: &Board, is_player: bool) -> (u32, u32, u32) {
         let mut total_player_shadow_area = 0;
         let mut enemy_trees_under_shadow = 0;
@@ -213,34 +278,44 @@ impl Game {
             .collect()
     }
 
-    fn find_shadows_by_player(
-        &self,
-        board: &Board,
-        sun_orientation: u8,
-        is_player: Option<bool>,
-    ) -> HashMap<u8, Shadow> {
-        let tree_iter = self
-            .trees()
-            .iter()
-            .filter(|x| is_player.is_none() || is_player == Some(x.is_mine()));
+    /// Shadows of all trees when the sun points to `direction`. Item `s` of the
+    /// result is the mask of cells in the shadow of a tree of size `s` or bigger.
+    pub fn shadow_mask(&self, board: &Board, direction: u8) -> [u64; 4] {
+        let mut by_size = [0u64; 4];
+        for size in 1..4u8 {
+            let mut trees = self.trees.size_mask(size);
+            while trees != 0 {
+                let index = trees.trailing_zeros() as u8;
+                trees &= trees - 1;
+                by_size[size as usize] |= board.shadow_line(index, direction, size);
+            }
+        }
+        [
+            by_size[1] | by_size[2] | by_size[3],
+            by_size[1] | by_size[2] | by_size[3],
+            by_size[2] | by_size[3],
+            by_size[3],
+        ]
+    }
 
-        tree_iter
-            .map(|t| {
-                self.find_shadows_by(board, t.index(), sun_orientation)
-                    .map(move |s| {
-                        (
-                            s,
-                            Shadow {
-                                index: s,
-                                size: t.size(),
-                                caused_by_player: t.is_mine(),
-                            },
-                        )
-                    })
-            })
-            .flatten()
-            .sorted_by_key(|x| x.1.size)
-            .collect()
+    /// Trees in the shadow of a tree of the same size or bigger. Seeds never
+    /// collect anything, so they are left out.
+    pub fn spooky_mask(&self, board: &Board, direction: u8) -> u64 {
+        if !self.rules.shadows_enabled {
+            return 0;
+        }
+        let shadows = self.shadow_mask(board, direction);
+        (1..4u8).fold(0, |mask, size| {
+            mask | (self.trees.size_mask(size) & shadows[size as usize])
+        })
+    }
+
+    /// Sun points the player would collect with the sun pointing to `direction`.
+    pub fn sun_income(&self, board: &Board, direction: u8, is_player: bool) -> u32 {
+        let collecting = self.trees.mask(is_player) & !self.spooky_mask(board, direction);
+        (1..4u8)
+            .map(|size| size as u32 * (self.trees.size_mask(size) & collecting).count_ones())
+            .sum()
     }
 
     fn pay_action_cost(&mut self, board: &Board, action: Action, is_player: bool) {
@@ -259,29 +334,17 @@ impl Game {
         }
     }
 
-    fn richness_to_points(richness: u8) -> u16 {
-        match richness {
-            1 => 0,
-            2 => 2,
-            3 => 4,
-            _ => panic!(
-                "richness of a tree can only be 1, 2 and 3. Got: {}",
-                richness
-            ),
-        }
-    }
-
     fn complete_tree(&mut self, board: &Board, tree_index: u8, is_player: bool) {
         let richness = board.get_richness(tree_index);
         self.trees.remove(tree_index);
         self.increase_points(
-            self.nutrients + Self::richness_to_points(richness),
+            self.nutrients + self.rules.richness_bonus(richness),
             is_player,
         );
     }
 
     pub fn apply_single_action(&self, board: &Board, action: Action, is_player: bool) -> Game {
-        let mut new_state = self.clone();
+        let mut new_state = *self;
         new_state.pay_action_cost(board, action, is_player);
         new_state.apply_action_on_clone(board, action, is_player);
         new_state
@@ -289,117 +352,122 @@ impl Game {
 
     fn apply_action_on_clone(&mut self, board: &Board, action: Action, is_player: bool) {
         match (action, is_player) {
-            (Action::WAIT, true) => {}
+            (Action::WAIT, true) => self.player_waiting = true,
             (Action::WAIT, false) => self.opponent_waiting = true,
             (Action::COMPLETE(t), _) => {
                 self.complete_tree(board, t, is_player);
-                self.nutrients -= 1;
+                self.nutrients = self.nutrients.saturating_sub(1);
             }
             (Action::GROW(x), _) => {
                 self.trees.grow_size(x);
             }
             (Action::SEED(from, to), is_player) => {
                 self.trees.seed(to, is_player);
-                self.trees.get_mut(from).set_dormant(true);
+                self.trees.set_dormant(from, true);
             }
         }
     }
 
     fn apply_seed_collision(&self, player_from: u8, enemy_from: u8) -> Game {
-        let mut new_state = self.clone();
-        new_state.trees.get_mut(player_from).set_dormant(true);
-        new_state.trees.get_mut(enemy_from).set_dormant(true);
+        let mut new_state = *self;
+        new_state.trees.set_dormant(player_from, true);
+        new_state.trees.set_dormant(enemy_from, true);
         return new_state;
     }
 
-    fn force_wait_when_no_points(&self, board: &Board, action: Action, sun_points: u16) -> Action {
-        if Action::get_action_cost(self, action, false) as u16 > sun_points {
-            Action::WAIT
-        } else {
-            action
+    /// Like the referee, plays an illegal action as WAIT. Callers that need the
+    /// reason should use `Action::validate` first.
+    fn actual_action(&self, board: &Board, action: Action, is_player: bool) -> Action {
+        match action.validate(self, board, is_player) {
+            Ok(()) => action,
+            Err(_) => Action::WAIT,
         }
     }
 
+    /// Plays one turn. The action of an asleep player is ignored, and a new
+    /// day starts once both players are asleep.
     pub fn apply_actions(&self, board: &Board, player: Action, enemy: Action) -> Game {
-        let player = self.force_wait_when_no_points(board, player, self.my_sun_points);
-        let enemy = self.force_wait_when_no_points(board, enemy, self.enemy_sun_points);
+        let player = self.actual_action(board, player, true);
+        let enemy = self.actual_action(board, enemy, false);
 
-        match (player, enemy) {
+        let new_state = match (player, enemy) {
             (Action::SEED(player_from, x), Action::SEED(enemy_from, y)) if x == y => {
                 self.apply_seed_collision(player_from, enemy_from)
             }
             (Action::COMPLETE(x), Action::COMPLETE(y)) => {
-                let mut new_state = self.clone();
+                let mut new_state = *self;
                 new_state.pay_action_cost(board, player, true);
                 new_state.pay_action_cost(board, enemy, false);
                 new_state.complete_tree(board, x, true);
                 new_state.complete_tree(board, y, false);
-                new_state.nutrients -= 2;
+                new_state.nutrients = new_state.nutrients.saturating_sub(2);
                 new_state
             }
-            (Action::WAIT, Action::WAIT) => self.apply_new_day(board),
             (player, enemy) => {
-                let mut new_state = self.clone();
+                let mut new_state = *self;
                 new_state.pay_action_cost(board, player, true);
                 new_state.pay_action_cost(board, enemy, false);
                 new_state.apply_action_on_clone(board, player, true);
                 new_state.apply_action_on_clone(board, enemy, false);
-                return new_state;
+                new_state
             }
+        };
+
+        if new_state.player_waiting && new_state.opponent_waiting {
+            new_state.apply_new_day(board)
+        } else {
+            new_state
         }
     }
 
-    fn collect_sun_points(&mut self, points: u8, is_player: bool) {
+    fn collect_sun_points(&mut self, points: u16, is_player: bool) {
         if is_player {
-            self.my_sun_points += points as u16;
+            self.my_sun_points += points;
         } else {
-            self.enemy_sun_points += points as u16;
+            self.enemy_sun_points += points;
         }
     }
 
     pub fn average_sun_income(&self, board: &Board, is_player: bool) -> u32 {
-        let x: u32 = (1..7u8)
-            .map(|o| {
-                let all_shadows = self.find_shadows_by_player(board, (self.day + o) % 6, None);
-                return self
-                    .trees
-                    .iter_trees_for(is_player)
-                    .filter(|t| match (&all_shadows.get(&t.index()), t.size()) {
-                        (Some(s), tree_size) if s.size >= tree_size => false,
-                        (_, _) => true,
-                    })
-                    .map(|x| x.size() as u32)
-                    .collect_vec();
-            })
-            .flatten()
+        let x: u32 = (0..6u8)
+            .map(|direction| self.sun_income(board, direction, is_player))
             .sum();
         return x / 6;
     }
 
+    /// The sun moves one step each day, and trees cast their shadows in the
+    /// direction it points to.
+    pub fn sun_direction(&self) -> u8 {
+        self.day % 6
+    }
+
+    /// Trees that are not spooky collect sun points. A tree is spooky when it
+    /// is in the shadow of a tree of the same size or bigger. Seeds never cast
+    /// shadows and don't collect anything.
     fn apply_sun_points_for(&mut self, board: &Board, is_player: bool) {
-        let all_shadows = self.find_shadows_by_player(board, self.day % 6, None);
-        let sun_trees: Vec<_> = self
-            .trees
-            .iter_trees_for(is_player)
-            .filter(|t| match (&all_shadows.get(&t.index()), t.size()) {
-                (Some(s), tree_size) if s.size >= tree_size => false,
-                (_, _) => true,
-            })
-            .map(|x| (x.index(), x.size()))
-            .collect();
-        for (_, size) in sun_trees {
-            self.collect_sun_points(size, is_player);
+        let income = self.sun_income(board, self.sun_direction(), is_player);
+        self.collect_sun_points(income as u16, is_player);
+    }
+
+    /// The gathering phase in the referee's order: the sun has already moved
+    /// with the day, then both players collect sun points. Nothing is
+    /// collected once the game is over. Day 0 starts with this phase too.
+    pub fn start_of_day(&self, board: &Board) -> Game {
+        let mut new_state = *self;
+        if !new_state.is_over() {
+            new_state.apply_sun_points_for(board, true);
+            new_state.apply_sun_points_for(board, false);
         }
+        new_state
     }
 
     pub fn apply_new_day(&self, board: &Board) -> Game {
-        let mut new_state = self.clone();
+        let mut new_state = *self;
         new_state.day += 1;
-        new_state.apply_sun_points_for(board, true);
-        new_state.apply_sun_points_for(board, false);
+        new_state.player_waiting = false;
         new_state.opponent_waiting = false;
         new_state.trees.wake_up();
-        new_state
+        new_state.start_of_day(board)
     }
 
     pub fn parse_from_strings(input: Vec<&str>) -> Game {
@@ -433,13 +501,163 @@ impl Game {
         );
     }
 
-    pub fn is_player_won(&self) -> bool {
-        match self.my_points.cmp(&self.enemy_points) {
-            Ordering::Less => false,
-            Ordering::Equal => self.my_sun_points > self.enemy_sun_points,
-            Ordering::Greater => true,
+    pub fn to_strings(&self) -> Vec<String> {
+        let mut result = vec![
+            self.day.to_string(),
+            self.nutrients.to_string(),
+            format!("{} {}", self.my_sun_points, self.my_points),
+            format!(
+                "{} {} {}",
+                self.enemy_sun_points, self.enemy_points, self.opponent_waiting as u8
+            ),
+            self.trees.iter().count().to_string(),
+        ];
+        result.extend(self.trees.iter().map(|t| t.to_string()));
+        result
+    }
+
+    /// Official end of game scoring: every 3 sun points left are worth one
+    /// point, and a tie goes to the player with more trees.
+    pub fn final_scores(&self) -> GameResult {
+        let player_score = self.my_points + self.my_sun_points / 3;
+        let enemy_score = self.enemy_points + self.enemy_sun_points / 3;
+        let winner = match player_score
+            .cmp(&enemy_score)
+            .then(self.trees.len(true).cmp(&self.trees.len(false)))
+        {
+            Ordering::Less => Some(false),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(true),
+        };
+        GameResult {
+            player_score,
+            enemy_score,
+            winner,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameResult {
+    pub player_score: u16,
+    pub enemy_score: u16,
+    /// `Some(true)` when the player won, `Some(false)` when the enemy won.
+    pub winner: Option<bool>,
+}
+
+impl GameResult {
+    pub fn score(&self, is_player: bool) -> u16 {
+        if is_player {
+            self.player_score
+        } else {
+            self.enemy_score
         }
     }
+
+    pub fn is_won_by(&self, is_player: bool) -> bool {
+        self.winner == Some(is_player)
+    }
+
+    pub fn is_draw(&self) -> bool {
+        self.winner.is_none()
+    }
+}
+
+/// One step of the best path found by `search_next_action`, with the game
+/// after both actions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathStep {
+    pub action: Action,
+    pub enemy_action: Action,
+    pub day: u8,
+    pub sun: u16,
+    /// Score of the step in the search.
+    pub score: i32,
+    pub game: Game,
+}
+
+/// What `search_next_action` found: the best path from the game, the score of
+/// the game at its end and how much was searched.
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub path: Vec<PathStep>,
+    pub breakdown: Score,
+    /// Games whose actions were expanded.
+    pub nodes: usize,
+    pub elapsed_ms: u128,
+}
+
+impl SearchResult {
+    pub fn depth(&self) -> u32 {
+        self.path.len() as u32
+    }
+
+    /// The first action of the path, WAIT when nothing was searched.
+    pub fn action(&self) -> Action {
+        self.path.first().map_or(Action::WAIT, |x| x.action)
+    }
+}
+
+/// How `search_next_action_against` searches.
+#[derive(Clone, Copy)]
+pub struct SearchSettings<'a> {
+    /// Our best actions tried in every game, and games expanded on every
+    /// level at most.
+    pub width: usize,
+    /// Milliseconds to search at most.
+    pub time_limit: u128,
+    pub opponent: &'a dyn OpponentModel,
+    pub aggregation: Aggregation,
+    /// A path found earlier from the game, put in the beam first.
+    pub plan: &'a [PathStep],
+}
+
+impl<'a> SearchSettings<'a> {
+    /// Against an enemy that waits, without a plan.
+    pub fn new(width: usize, time_limit: u128) -> Self {
+        Self {
+            width,
+            time_limit,
+            opponent: &WaitingOpponent,
+            aggregation: Aggregation::WorstCase,
+            plan: &[],
+        }
+    }
+
+    pub fn with_opponent(
+        mut self,
+        opponent: &'a dyn OpponentModel,
+        aggregation: Aggregation,
+    ) -> Self {
+        self.opponent = opponent;
+        self.aggregation = aggregation;
+        self
+    }
+
+    pub fn with_plan(mut self, plan: &'a [PathStep]) -> Self {
+        self.plan = plan;
+        self
+    }
+}
+
+impl Display for SearchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "depth: {}, nodes: {}, elapsed: {} ms",
+            self.depth(),
+            self.nodes,
+            self.elapsed_ms
+        )?;
+        for step in &self.path {
+            writeln!(
+                f,
+                "  {} / {} -> day: {}, sun: {}, score: {}",
+                step.action, step.enemy_action, step.day, step.sun, step.score
+            )?;
+        }
+        write!(f, "final: {}", self.breakdown)
+    }
 }
 
 fn get_best_actions(
@@ -448,19 +666,35 @@ fn get_best_actions(
     width: usize,
     is_player: bool,
 ) -> impl Iterator<Item = Action> {
-    Action::find_next_actions(&game, &board, is_player)
-        .into_iter()
-        .sorted_by(|x, y| compare(&game, &board, x, y).reverse())
-        .take(width)
+    let actions = Action::find_next_actions(game, board, is_player);
+    top_n(actions, width, |x, y| compare(game, board, x, y)).into_iter()
 }
 
 pub fn search_next_action(
     game: &Game,
     board: &Board,
     width: usize,
-    width_at_level: usize,
     time_limit: u128,
-) -> (u32, Action) {
+) -> SearchResult {
+    search_next_action_against(game, board, SearchSettings::new(width, time_limit))
+}
+
+/// Beam search of our actions against the responses of the opponent of
+/// `settings`. Every action is scored against all of them, combined by the
+/// aggregation. The steps of the plan, a path found earlier from `game`, are
+/// put in the beam first, as far as they still follow from each other.
+pub fn search_next_action_against(
+    game: &Game,
+    board: &Board,
+    settings: SearchSettings,
+) -> SearchResult {
+    let SearchSettings {
+        width,
+        time_limit,
+        opponent,
+        aggregation,
+        plan,
+    } = settings;
     let mut games = vec![game.clone()];
     let mut heap = BinaryHeap::new();
     let mut moves = Vec::new();
@@ -468,6 +702,33 @@ pub fn search_next_action(
     let start = Instant::now();
     let mut best_at_level = Vec::<Option<usize>>::new();
     let mut iterations_on_level = 0;
+    let mut seen = HashSet::new();
+    seen.insert(game.key());
+    let mut nodes = 0;
+
+    let mut parent = (0, None);
+    for (level, step) in plan.iter().enumerate() {
+        let next = games[parent.0].apply_actions(board, step.action, step.enemy_action);
+        if next != step.game || !seen.insert(next.key()) {
+            break;
+        }
+        heap.push((
+            Reverse(level + 1),
+            step.score,
+            games.len(),
+            Some(moves.len()),
+        ));
+        games.push(next);
+        moves.push((
+            parent.1,
+            step.action,
+            step.enemy_action,
+            games.len() - 1,
+            step.score,
+        ));
+        parent = (games.len() - 1, Some(moves.len() - 1));
+    }
+    let (planned, planned_depth) = (parent.1, moves.len());
 
     while let Some((Reverse(level), score, game_id, move_id)) = heap.pop() {
         if best_at_level.len() == level {
@@ -485,40 +746,69 @@ pub fn search_next_action(
         if now.duration_since(start).as_millis() > time_limit {
             break;
         }
-        if games[game_id].day == 24 {
+        if games[game_id].is_over() {
             continue;
         }
-        for p_action in get_best_actions(&games[game_id], &board, width, true) {
-            for e_action in vec![Action::WAIT] {
-                let new_game = games[game_id].apply_actions(&board, p_action, e_action);
-                /*eprintln!(
-                    "L:{}, {}. Score: {:?}",
-                    level,
-                    p_action,
-                    Simulation::get_score(&new_game, &board, true),
-                );*/
-
-                heap.push((
-                    std::cmp::Reverse(level + 1),
-                    Simulation::get_score(&new_game, &board, true).value(),
-                    games.len(),
-                    Some(moves.len()),
-                ));
-                games.push(new_game);
-                moves.push((move_id, p_action, e_action, game_id))
+        nodes += 1;
+        let responses = opponent.responses(&games[game_id], board);
+        for p_action in get_best_actions(&games[game_id], board, width, true) {
+            let outcomes = responses.iter().map(|e_action| {
+                let new_game = games[game_id].apply_actions(board, p_action, *e_action);
+                let score = Simulation::get_score(&new_game, board, true).value();
+                (score, *e_action, new_game)
+            });
+            let (score, e_action, new_game) = match aggregation {
+                Aggregation::WorstCase => outcomes.min_by_key(|x| x.0).unwrap(),
+                Aggregation::Expectation => {
+                    let outcomes = outcomes.collect_vec();
+                    let total: i32 = outcomes.iter().map(|x| x.0).sum();
+                    let (_, e_action, new_game) = outcomes[0];
+                    (total / outcomes.len() as i32, e_action, new_game)
+                }
+            };
+            if !seen.insert(new_game.key()) {
+                continue;
             }
+
+            heap.push((
+                std::cmp::Reverse(level + 1),
+                score,
+                games.len(),
+                Some(moves.len()),
+            ));
+            games.push(new_game);
+            moves.push((move_id, p_action, e_action, games.len() - 1, score))
         }
     }
 
-    let mut last_item = best_at_level.last().unwrap().unwrap();
+    let mut path = Vec::new();
+    // The rest of the plan may go deeper than the search got this time.
+    let mut last_item = if planned_depth >= best_at_level.len() {
+        planned
+    } else {
+        best_at_level.last().cloned().flatten()
+    };
+    let last_game = last_item.map_or(game, |item| &games[moves[item].3]);
+    while let Some(item) = last_item {
+        let (parent_move, action, enemy_action, game_id, score) = moves[item];
+        let game = &games[game_id];
+        path.push(PathStep {
+            action,
+            enemy_action,
+            day: game.day,
+            sun: game.get_sun_points(true),
+            score,
+            game: *game,
+        });
+        last_item = parent_move;
+    }
+    path.reverse();
 
-    loop {
-        let (parent_move, player, enemy, _) = moves[last_item];
-        if let Some(next) = parent_move {
-            last_item = next;
-        } else {
-            return (best_at_level.len() as u32, player);
-        }
+    SearchResult {
+        breakdown: Simulation::get_score(last_game, board, true),
+        path,
+        nodes,
+        elapsed_ms: start.elapsed().as_millis(),
     }
 }
 
@@ -533,7 +823,7 @@ pub fn compare(game: &Game, board: &Board, x: &Action, y: &Action) -> Ordering {
         .apply_new_day(board);
 
     fn can_harvest(game: &Game, size: u8) -> bool {
-        24 - game.day >= 4 - size
+        game.rules.max_days - game.day >= 4 - size
     }
 
     match (x, y) {
@@ -604,8 +894,9 @@ fn greater_if_true(value: bool) -> Ordering {
 
 #[cfg(test)]
 mod tests {
-    use std::vec;
+    use std::{fs, path::Path, vec};
 
+    use super::super::{board::Cell, opponent::BestResponses};
     use super::*;
 
     #[test]
@@ -644,6 +935,316 @@ mod tests {
         assert_eq!(game, expected_state);
     }
 
+    #[test]
+    fn test_to_strings() {
+        let game_strs = vec![
+            "3", "20", "4 0", "3 0 1", "4", "3 0 0 1", "6 0 1 1", "8 1 0 1", "17 1 1 1",
+        ];
+        let game = Game::parse_from_strings(game_strs.clone());
+
+        assert_eq!(game.to_strings(), game_strs);
+    }
+
+    #[test]
+    fn test_complete_without_nutrients() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec!["23", "0", "8 10", "0 20 1", "1", "0 3 1 0"]);
+        let game = game.apply_actions(&board, Action::COMPLETE(0), Action::WAIT);
+
+        assert_eq!((game.nutrients, game.get_points(true)), (0, 14));
+    }
+
+    #[test]
+    fn test_asleep_player_skips_the_rest_of_the_day() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "3", "20", "10 0", "10 0 0", "3", "1 1 1 0", "4 1 0 0", "5 1 0 0",
+        ]);
+
+        let game = game.apply_actions(&board, Action::WAIT, Action::GROW(4));
+        assert_eq!((game.day, game.is_waiting(true)), (3, true));
+        assert_eq!(
+            Action::find_next_actions(&game, &board, true),
+            vec![Action::WAIT]
+        );
+
+        let game = game.apply_actions(&board, Action::GROW(1), Action::SEED(5, 6));
+        assert_eq!(game.trees().get(1).size(), 1);
+        assert_eq!(game.trees().len(false), 3);
+
+        let game = game.apply_actions(&board, Action::WAIT, Action::WAIT);
+        assert_eq!(game.day, 4);
+        assert!(!game.is_waiting(true) && !game.is_waiting(false));
+    }
+
+    #[test]
+    fn test_key_of_transpositions() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "3", "20", "10 0", "10 0 0", "3", "1 1 1 0", "2 1 1 0", "5 1 0 0",
+        ]);
+        let first = game
+            .apply_actions(&board, Action::GROW(1), Action::WAIT)
+            .apply_actions(&board, Action::GROW(2), Action::WAIT);
+        let second = game
+            .apply_actions(&board, Action::GROW(2), Action::WAIT)
+            .apply_actions(&board, Action::GROW(1), Action::WAIT);
+
+        assert_eq!(first.key(), second.key());
+        assert_ne!(first.key(), game.key());
+        assert_ne!(first.key(), first.swap_players().key());
+        let parsed: Vec<String> = first.to_strings();
+        let parsed = Game::parse_from_strings(parsed.iter().map(|x| x.as_str()).collect());
+        assert_eq!(parsed.key(), first.key());
+    }
+
+    #[test]
+    fn test_swap_players() {
+        let game =
+            Game::parse_from_strings(vec!["3", "20", "4 1", "3 2 1", "2", "3 0 0 1", "17 1 1 0"]);
+
+        assert_eq!(
+            game.swap_players().to_strings(),
+            vec!["3", "20", "3 2", "4 1 0", "2", "3 0 1 1", "17 1 0 0"]
+        );
+        assert_eq!(game.swap_players().swap_players(), game);
+    }
+
+    #[test]
+    fn test_sun_direction() {
+        let game = Game::parse_from_strings(vec!["7", "20", "0 0", "0 0 0", "0"]);
+        assert_eq!(game.sun_direction(), 1);
+    }
+
+    #[test]
+    fn test_start_of_day_0() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "0 0", "0 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+
+        assert_eq!(
+            game.start_of_day(&board).to_strings(),
+            vec!["0", "20", "2 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0"]
+        );
+    }
+
+    #[test]
+    fn test_new_day_collects_with_shadows() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "5", "20", "0 0", "0 0 0", "5", "0 3 1 1", "1 2 0 1", "4 2 0 0", "9 0 1 0", "10 1 0 0",
+        ]);
+        let game = game.apply_actions(&board, Action::WAIT, Action::WAIT);
+
+        // The sun points to direction 0: the tree on 1 is spooky, the tree on
+        // 0 is only shaded by a smaller tree and the seed on 9 collects nothing.
+        assert_eq!(
+            game.to_strings(),
+            vec![
+                "6", "20", "3 0", "3 0 0", "5", "0 3 1 0", "1 2 0 0", "4 2 0 0", "9 0 1 0",
+                "10 1 0 0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_sun_after_last_day() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec!["23", "20", "5 0", "5 0 0", "1", "0 3 1 0"]);
+        let game = game.apply_actions(&board, Action::WAIT, Action::WAIT);
+
+        assert_eq!((game.day, game.get_sun_points(true)), (MAX_DAYS, 5));
+    }
+
+    /// Checks a recorded match, the stdin of one bot for a whole game, which
+    /// the bot also echoes to stderr. Our legal moves must be the listed ones
+    /// and every turn must follow from the one before by legal actions of
+    /// both players, the enemy acting alone while we sleep. Returns the
+    /// number of turns.
+    fn replay_log(log: &str) -> Result<usize, String> {
+        let lines: Vec<&str> = log
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect();
+        let mut i = 0;
+        let cells: usize = Next::read_from(&lines, &mut i);
+        let board: Board = (0..cells)
+            .map(|_| Next::<Cell>::read_from(&lines, &mut i))
+            .collect();
+
+        let mut turns = Vec::new();
+        while i < lines.len() {
+            let trees: usize = lines[i + 4].parse().unwrap();
+            let game = Game::parse_from_strings(lines[i..i + 5 + trees].to_vec());
+            i += 5 + trees;
+            let moves: usize = Next::read_from(&lines, &mut i);
+            let mut listed: Vec<String> =
+                lines[i..i + moves].iter().map(|x| x.to_string()).collect();
+            i += moves;
+
+            let mut legal: Vec<String> = Action::find_next_actions(&game, &board, true)
+                .iter()
+                .map(|a| a.to_string())
+                .collect();
+            listed.sort();
+            legal.sort();
+            if listed != legal {
+                return Err(format!(
+                    "day {}: moves {:?}, expected {:?}",
+                    game.day, legal, listed
+                ));
+            }
+            turns.push(game);
+        }
+
+        for pair in turns.windows(2) {
+            if !is_reachable(&board, &pair[0], &pair[1]) {
+                return Err(format!("{} does not lead to {}", pair[0], pair[1]));
+            }
+        }
+        Ok(turns.len())
+    }
+
+    /// Whether `to` follows from `from` by one pair of actions, and then by
+    /// enemy actions alone if we went to sleep. Enemy actions that don't lead
+    /// to the trees of `to` are not tried.
+    fn is_reachable(board: &Board, from: &Game, to: &Game) -> bool {
+        let target = to.to_strings();
+        let mut asleep = Vec::new();
+        let mut seen = HashSet::new();
+        for player in Action::find_next_actions(from, board, true) {
+            for enemy in Action::find_next_actions(from, board, false) {
+                let next = from.apply_actions(board, player, enemy);
+                if next.to_strings() == target {
+                    return true;
+                }
+                if next.is_waiting(true) && next.day == from.day && seen.insert(next.key()) {
+                    asleep.push(next);
+                }
+            }
+        }
+
+        let leads_to_target = |game: &Game, action: &Action| match *action {
+            Action::GROW(x) => {
+                to.trees().has_at(x) && to.trees().get(x).size() > game.trees().get(x).size()
+            }
+            Action::SEED(_, x) => to.trees().has_at(x),
+            _ => true,
+        };
+        while let Some(game) = asleep.pop() {
+            for enemy in Action::find_next_actions(&game, board, false) {
+                if !leads_to_target(&game, &enemy) {
+                    continue;
+                }
+                let next = game.apply_actions(board, Action::WAIT, enemy);
+                if next.to_strings() == target {
+                    return true;
+                }
+                if next.day == game.day && seen.insert(next.key()) {
+                    asleep.push(next);
+                }
+            }
+        }
+        false
+    }
+
+    fn write_turn(log: &mut Vec<String>, game: &Game, board: &Board) {
+        log.extend(game.to_strings());
+        let moves = Action::find_next_actions(game, board, true);
+        log.push(moves.len().to_string());
+        log.extend(moves.iter().map(|a| a.to_string()));
+    }
+
+    #[test]
+    fn test_replay_log() {
+        let board = Board::default();
+        let mut log: Vec<String> = vec![board.cells().count().to_string()];
+        log.extend(board.cells().map(|c| c.to_string()));
+        let game = Game::parse_from_strings(vec![
+            "3", "20", "6 0", "20 0 0", "4", "20 1 1 0", "24 1 1 0", "29 1 0 0", "33 1 0 0",
+        ]);
+        write_turn(&mut log, &game, &board);
+        let game = game.apply_actions(&board, Action::GROW(20), Action::GROW(29));
+        write_turn(&mut log, &game, &board);
+        let asleep = game.apply_actions(&board, Action::WAIT, Action::GROW(33));
+        let game = asleep.apply_actions(&board, Action::WAIT, Action::WAIT);
+        let last = log.len();
+        write_turn(&mut log, &game, &board);
+
+        assert_eq!(game.day, 4);
+        assert_eq!(replay_log(&log.join("\n")), Ok(3));
+
+        // One sun point more than the shadows allow on the new day.
+        let sun = game.get_sun_points(true) + 1;
+        log[last + 2] = format!("{} {}", sun, game.get_points(true));
+        assert!(replay_log(&log.join("\n")).is_err());
+    }
+
+    /// Every file in `tests/logs` is a recorded match for `replay_log`.
+    #[test]
+    fn test_recorded_logs() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/logs");
+        let mut logs = 0;
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let log = fs::read_to_string(&path).unwrap();
+            assert_eq!(replay_log(&log).map(|_| ()), Ok(()), "{}", path.display());
+            logs += 1;
+        }
+        assert!(logs > 0);
+    }
+
+    #[test]
+    fn test_wood_1_rules() {
+        let board = Board::default();
+        let game =
+            Game::parse_from_strings(vec!["4", "20", "7 0", "0 0 0", "2", "0 3 1 0", "6 2 0 0"])
+                .with_rules(Rules::wood_1());
+
+        assert_eq!(
+            Action::find_next_actions(&game, &board, true),
+            vec![Action::WAIT, Action::COMPLETE(0)]
+        );
+
+        // Without shadows the tree on 6 collects sun on day 5 as well.
+        let game = game.apply_actions(&board, Action::WAIT, Action::WAIT);
+        assert_eq!(game.get_sun_points(false), 2);
+
+        let game = game.apply_actions(&board, Action::WAIT, Action::WAIT);
+        assert!(game.is_over());
+        assert_eq!(game.get_sun_points(false), 2);
+    }
+
+    #[test]
+    fn test_final_scores_count_sun() {
+        let game = Game::parse_from_strings(vec!["24", "0", "8 60", "2 61 0", "0"]);
+        let result = game.final_scores();
+
+        assert_eq!((result.player_score, result.enemy_score), (62, 61));
+        assert!(result.is_won_by(true));
+    }
+
+    #[test]
+    fn test_final_scores_tie_break_by_trees() {
+        let game = Game::parse_from_strings(vec![
+            "24", "0", "3 60", "5 60 0", "3", "1 1 1 0", "2 0 0 0", "3 3 1 0",
+        ]);
+        let result = game.final_scores();
+
+        assert_eq!((result.player_score, result.enemy_score), (61, 61));
+        assert!(result.is_won_by(true));
+    }
+
+    #[test]
+    fn test_final_scores_draw() {
+        let game =
+            Game::parse_from_strings(vec!["24", "0", "2 61", "0 61 0", "2", "1 1 1 0", "2 0 0 0"]);
+
+        assert!(game.final_scores().is_draw());
+    }
+
     #[test]
     fn test_moves_ahead() {
         let board = Board::default();
@@ -741,7 +1342,63 @@ mod tests {
         ];
         let game = Game::parse_from_strings(game_strs);
 
-        let next_move = search_next_action(&game, &board, 10, 10, 100);
-        println!("{}-{}", next_move.0, next_move.1);
+        let result = search_next_action(&game, &board, 10, 100);
+        println!("{}", result);
+
+        assert!(result.depth() > 0);
+        assert!(result.nodes > 0);
+        assert_eq!(result.action(), result.path[0].action);
+        assert!(result.path.windows(2).all(|x| x[0].day <= x[1].day));
+        let mut previous = game;
+        for step in &result.path {
+            let next = previous.apply_actions(&board, step.action, step.enemy_action);
+            assert_eq!(step.game, next);
+            assert_eq!(step.day, next.day);
+            assert_eq!(step.sun, next.get_sun_points(true));
+            previous = next;
+        }
+        assert_eq!(
+            result.breakdown,
+            Simulation::get_score(&previous, &board, true)
+        );
+    }
+
+    #[test]
+    fn test_search_continues_plan() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "10", "20", "12 0", "12 0 0", "4", "20 1 0 0", "24 1 1 0", "29 3 0 0", "33 3 1 0",
+        ]);
+        let search = |game: &Game, plan: &[PathStep], time_limit: u128| {
+            let settings = SearchSettings::new(5, time_limit).with_plan(plan);
+            search_next_action_against(game, &board, settings)
+        };
+        let first = search(&game, &[], 50);
+        assert!(first.depth() > 2);
+
+        let next = search(&first.path[0].game, &first.path[1..], 1);
+        assert!(next.depth() >= first.depth() - 1);
+
+        let mut wrong = first.path.clone();
+        wrong[1].game = game;
+        let next = search(&first.path[0].game, &wrong[1..], 1);
+        assert!(next.path.iter().all(|x| x.game != game));
+    }
+
+    #[test]
+    fn test_search_against_best_responses() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "10", "20", "12 0", "12 0 0", "4", "20 1 0 0", "24 1 1 0", "29 3 0 0", "33 3 1 0",
+        ]);
+        let legal = Action::find_next_actions(&game, &board, true);
+        for aggregation in [Aggregation::WorstCase, Aggregation::Expectation] {
+            let opponent = BestResponses { k: 3 };
+            let settings = SearchSettings::new(5, 20).with_opponent(&opponent, aggregation);
+            let result = search_next_action_against(&game, &board, settings);
+
+            assert!(result.depth() > 1);
+            assert!(legal.contains(&result.action()));
+        }
     }
 }
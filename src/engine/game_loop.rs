@@ -7,6 +7,7 @@ use super::{
     board::{Board, Cell},
     game::Game,
     parse::Next,
+    rules::Rules,
 };
 
 pub trait Strategy {
@@ -22,11 +23,20 @@ pub struct GameLoop {
     time_limit: usize,
 }
 
-pub struct GameSettings {}
+pub struct GameSettings {
+    rules: Rules,
+}
 
 impl GameSettings {
     pub fn new() -> GameSettings {
-        GameSettings {}
+        GameSettings {
+            rules: Rules::default(),
+        }
+    }
+
+    pub fn with_rules(mut self, rules: Rules) -> GameSettings {
+        self.rules = rules;
+        self
     }
 }
 
@@ -80,9 +90,13 @@ where
             opp_score,
             day,
             opp_is_waiting == 1,
-        );
+        )
+        .with_rules(settings.rules);
         let start = Instant::now();
         let action = strat.get_next(&game, &board, time_limit);
+        if let Err(reason) = action.validate(&game, &board, true) {
+            eprintln!("illegal {}: {}", action, reason);
+        }
         let finish = Instant::now();
         eprintln!("elapsed: {} ms", finish.duration_since(start).as_millis(),);
         // Simulation::print_simulation(&sim, 0, 0, 1);
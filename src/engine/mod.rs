@@ -3,6 +3,13 @@ pub mod board;
 pub mod common;
 pub mod game;
 pub mod game_loop;
+pub mod opponent;
+pub mod parallel;
 pub mod parse;
+pub mod perft;
+pub mod planner;
+pub mod rules;
 pub mod simulation;
+pub mod strategies;
 pub mod tree;
+pub mod zobrist;
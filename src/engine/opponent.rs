@@ -0,0 +1,66 @@
+use super::{
+    actions::Action,
+    board::Board,
+    common::top_n,
+    game::{compare, Game},
+};
+
+/// Guesses what the enemy plays, for searches that only plan our actions.
+pub trait OpponentModel {
+    /// Likely enemy actions in `game`, most likely first. Never empty.
+    fn responses(&self, game: &Game, board: &Board) -> Vec<Action>;
+}
+
+/// An enemy that always waits.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WaitingOpponent;
+
+impl OpponentModel for WaitingOpponent {
+    fn responses(&self, _game: &Game, _board: &Board) -> Vec<Action> {
+        vec![Action::WAIT]
+    }
+}
+
+/// The `k` best enemy actions by `compare`, seen from the enemy's side.
+#[derive(Debug, Clone, Copy)]
+pub struct BestResponses {
+    pub k: usize,
+}
+
+impl OpponentModel for BestResponses {
+    fn responses(&self, game: &Game, board: &Board) -> Vec<Action> {
+        let enemy_view = game.swap_players();
+        let actions = Action::find_next_actions(&enemy_view, board, true);
+        top_n(actions, self.k.max(1), |x, y| {
+            compare(&enemy_view, board, x, y)
+        })
+    }
+}
+
+/// How the scores of our action against each enemy response are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    /// The lowest score, the search goes on after that response.
+    WorstCase,
+    /// The average score, the search goes on after the most likely response.
+    Expectation,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_best_responses_are_enemy_actions() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "10", "20", "0 0", "12 0 0", "4", "20 1 0 0", "24 1 0 0", "29 3 0 0", "33 1 1 0",
+        ]);
+        let responses = BestResponses { k: 3 }.responses(&game, &board);
+
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0], Action::COMPLETE(29));
+        let legal = Action::find_next_actions(&game, &board, false);
+        assert!(responses.iter().all(|a| legal.contains(a)));
+    }
+}
@@ -0,0 +1,147 @@
+use std::{cmp::Ordering, thread, time::Instant};
+
+use super::{
+    actions::Action,
+    board::Board,
+    game::Game,
+    simulation::{RootSelection, SearchStats, Simulation, UCB_CONSTANT},
+};
+
+/// One of our actions in the root, with the statistics of all the searches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RootMove {
+    pub action: Action,
+    pub picks: u32,
+    pub total_score: i64,
+}
+
+impl RootMove {
+    pub fn avg_score(&self) -> f64 {
+        self.total_score as f64 / self.picks as f64
+    }
+}
+
+/// Root parallel search: independent `Simulation`s of the same game on
+/// `threads` threads, with the statistics of the root actions merged.
+#[derive(Debug, Clone, Copy)]
+pub struct ParallelSearch {
+    pub threads: usize,
+    pub depth: u32,
+    pub width: usize,
+    /// The UCB constant of every search.
+    pub exploration: f64,
+}
+
+/// The merged root of a parallel search. In `stats` iterations and states
+/// are summed over the searches and `best_action` is the most picked one.
+#[derive(Debug, Clone)]
+pub struct ParallelResult {
+    pub moves: Vec<RootMove>,
+    pub stats: SearchStats,
+}
+
+impl ParallelSearch {
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads,
+            depth: 6,
+            width: 10,
+            exploration: UCB_CONSTANT,
+        }
+    }
+
+    /// Searches `game` on every thread for `time_budget` milliseconds.
+    pub fn run(&self, board: &Board, game: &Game, time_budget: u32) -> ParallelResult {
+        let start = Instant::now();
+        let searches: Vec<(Vec<RootMove>, SearchStats)> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..self.threads.max(1))
+                .map(|_| scope.spawn(|| self.search(board, game, time_budget)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        let mut result = ParallelResult {
+            moves: Vec::new(),
+            stats: SearchStats::default(),
+        };
+        for (moves, stats) in searches {
+            for m in moves {
+                match result.moves.iter_mut().find(|x| x.action == m.action) {
+                    Some(merged) => {
+                        merged.picks += m.picks;
+                        merged.total_score += m.total_score;
+                    }
+                    None => result.moves.push(m),
+                }
+            }
+            result.stats.iterations += stats.iterations;
+            result.stats.states += stats.states;
+            result.stats.max_depth = result.stats.max_depth.max(stats.max_depth);
+        }
+        result.stats.elapsed_ms = start.elapsed().as_millis();
+        result.stats.best_action = result.best_action(RootSelection::MostPicks);
+        result
+    }
+
+    fn search(&self, board: &Board, game: &Game, time_budget: u32) -> (Vec<RootMove>, SearchStats) {
+        let mut sim = Simulation::new(board, *game);
+        sim.set_exploration(self.exploration);
+        let root = sim.current_state();
+        let stats = sim.simulate3(root, self.depth, self.width, time_budget);
+        let moves = sim
+            .get_moves_summary()
+            .map(|p| RootMove {
+                action: p.action,
+                picks: p.picks,
+                total_score: p.total_score as i64,
+            })
+            .collect();
+        (moves, stats)
+    }
+}
+
+impl ParallelResult {
+    /// Picks like `Simulation::best_action` from the merged statistics.
+    pub fn best_action(&self, selection: RootSelection) -> Option<Action> {
+        let moves = self.moves.iter();
+        let best = match selection {
+            RootSelection::MostPicks => moves.max_by(|x, y| {
+                (x.picks, x.avg_score())
+                    .partial_cmp(&(y.picks, y.avg_score()))
+                    .unwrap_or(Ordering::Equal)
+            }),
+            RootSelection::BestAverage => moves.filter(|x| x.picks > 0).max_by(|x, y| {
+                x.avg_score()
+                    .partial_cmp(&y.avg_score())
+                    .unwrap_or(Ordering::Equal)
+            }),
+        };
+        best.map(|m| m.action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parallel_merges_roots() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "2 0", "2 0 0", "4", "24 1 1 0", "27 1 1 0", "33 1 0 0", "36 1 0 0",
+        ]);
+        let result = ParallelSearch::new(3).run(&board, &game, 20);
+
+        let picks: u32 = result.moves.iter().map(|m| m.picks).sum();
+        assert_eq!(picks, result.stats.iterations);
+        assert!(result.stats.iterations >= 3 * 8);
+        let legal = Action::find_next_actions(&game, &board, true);
+        for (i, m) in result.moves.iter().enumerate() {
+            assert!(legal.contains(&m.action));
+            assert!(result.moves[i + 1..].iter().all(|x| x.action != m.action));
+        }
+        let best = result.stats.best_action.unwrap();
+        assert_eq!(result.best_action(RootSelection::MostPicks), Some(best));
+        assert!(result.best_action(RootSelection::BestAverage).is_some());
+    }
+}
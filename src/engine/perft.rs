@@ -0,0 +1,109 @@
+use super::{actions::Action, board::Board, game::Game};
+
+/// Counts the leaves of the full game tree `depth` turns deep. Every turn is a
+/// pair of player and enemy actions, and a finished game is a leaf.
+pub fn perft(game: &Game, board: &Board, depth: u32) -> u64 {
+    if depth == 0 || game.is_over() {
+        return 1;
+    }
+    joint_moves(game, board)
+        .map(|(player, enemy)| perft(&game.apply_actions(board, player, enemy), board, depth - 1))
+        .sum()
+}
+
+/// The perft count below each joint move of the first turn, to find the move
+/// where two versions of the generator disagree.
+pub fn perft_divide(game: &Game, board: &Board, depth: u32) -> Vec<((Action, Action), u64)> {
+    if depth == 0 || game.is_over() {
+        return Vec::new();
+    }
+    joint_moves(game, board)
+        .map(|(player, enemy)| {
+            let next = game.apply_actions(board, player, enemy);
+            ((player, enemy), perft(&next, board, depth - 1))
+        })
+        .collect()
+}
+
+fn joint_moves<'a>(game: &Game, board: &Board) -> impl Iterator<Item = (Action, Action)> + 'a {
+    let player_moves = Action::find_next_actions(game, board, true);
+    let enemy_moves = Action::find_next_actions(game, board, false);
+    player_moves
+        .into_iter()
+        .flat_map(move |p| enemy_moves.clone().into_iter().map(move |e| (p, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_position() -> (Board, Game) {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "2 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        (board, game)
+    }
+
+    fn middle_position() -> (Board, Game) {
+        let board = Board::default_with_inactive(vec![25, 23, 32, 34].into_iter());
+        let game = Game::parse_from_strings(vec![
+            "7", "18", "11 2", "10 0 0", "8", "5 2 0 0", "6 1 0 0", "13 0 0 0", "15 2 0 0",
+            "21 3 1 0", "27 1 0 0", "30 1 0 0", "36 2 1 0",
+        ]);
+        (board, game)
+    }
+
+    fn last_day_position() -> (Board, Game) {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "23", "3", "6 40", "4 38 0", "6", "19 3 1 0", "7 0 1 0", "8 0 1 0", "28 3 0 0",
+            "16 0 0 0", "17 1 0 0",
+        ]);
+        (board, game)
+    }
+
+    #[test]
+    fn test_depth_1_is_product_of_moves() {
+        for (board, game) in [start_position(), middle_position(), last_day_position()] {
+            let expected = Action::find_next_actions(&game, &board, true).len()
+                * Action::find_next_actions(&game, &board, false).len();
+            assert_eq!(perft(&game, &board, 1), expected as u64);
+        }
+    }
+
+    #[test]
+    fn test_divide_sums_to_perft() {
+        let (board, game) = start_position();
+        let total: u64 = perft_divide(&game, &board, 3).iter().map(|x| x.1).sum();
+
+        assert_eq!(total, perft(&game, &board, 3));
+    }
+
+    #[test]
+    fn test_game_over_is_a_leaf() {
+        let (board, game) = last_day_position();
+        let game = game.apply_actions(&board, Action::WAIT, Action::WAIT);
+
+        assert_eq!(perft(&game, &board, 3), 1);
+        assert!(perft_divide(&game, &board, 3).is_empty());
+    }
+
+    #[test]
+    fn test_reference_counts() {
+        let (board, game) = start_position();
+        assert_eq!(
+            (1..4).map(|d| perft(&game, &board, d)).collect::<Vec<_>>(),
+            vec![81, 1801, 14881]
+        );
+
+        let (board, game) = middle_position();
+        assert_eq!(perft(&game, &board, 1), 975);
+
+        let (board, game) = last_day_position();
+        assert_eq!(
+            (1..4).map(|d| perft(&game, &board, d)).collect::<Vec<_>>(),
+            vec![384, 16797, 77157]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::{cmp::Reverse, collections::HashSet, time::Instant};
+
+use super::{actions::Action, board::Board, common::top_n, game::Game, simulation::Simulation};
+
+/// Beam search whose step is a whole day of our actions, ended by WAIT and
+/// followed by `Game::apply_new_day`. The enemy is assumed to wait.
+#[derive(Debug, Clone, Copy)]
+pub struct DayPlanner {
+    /// Games kept after every day.
+    pub width: usize,
+    /// Our best actions by `Simulation::weight_of` tried at every step.
+    pub branching: usize,
+    /// Actions before WAIT in a day at most.
+    pub max_actions: usize,
+    /// Days to look ahead at most.
+    pub horizon: u32,
+}
+
+/// Our actions for the rest of the day, the last one is WAIT.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayPlan {
+    pub actions: Vec<Action>,
+    /// Days looked ahead, this one included. The last one may be searched
+    /// from the best games only.
+    pub days: u32,
+    pub score: i32,
+}
+
+impl Default for DayPlanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    /// The plan of the first day that led here.
+    first: usize,
+    game: Game,
+    score: i32,
+}
+
+impl DayPlanner {
+    pub fn new() -> Self {
+        Self {
+            width: 10,
+            branching: 3,
+            max_actions: 3,
+            horizon: 6,
+        }
+    }
+
+    /// Every plan for the rest of the day with the game on the next morning.
+    /// Plans that reach the same game in another order are left out.
+    pub fn day_plans(&self, game: &Game, board: &Board) -> Vec<(Vec<Action>, Game)> {
+        let mut plans = Vec::new();
+        let mut seen = HashSet::new();
+        let mut open = vec![(Vec::new(), *game)];
+        while let Some((actions, game)) = open.pop() {
+            let mut plan = actions.clone();
+            plan.push(Action::WAIT);
+            let morning = game
+                .apply_single_action(board, Action::WAIT, true)
+                .apply_new_day(board);
+            plans.push((plan, morning));
+
+            if actions.len() >= self.max_actions || game.is_over() {
+                continue;
+            }
+            let candidates: Vec<Action> = Action::find_next_actions(&game, board, true)
+                .into_iter()
+                .filter(|a| *a != Action::WAIT)
+                .collect();
+            let weight = |a: &Action| Simulation::weight_of(&game, board, a);
+            for action in top_n(candidates, self.branching, |x, y| weight(x).cmp(&weight(y))) {
+                let next = game.apply_single_action(board, action, true);
+                if seen.insert(next.key()) {
+                    let mut next_actions = actions.clone();
+                    next_actions.push(action);
+                    open.push((next_actions, next));
+                }
+            }
+        }
+        plans
+    }
+
+    /// The plan for today whose game scores best after up to `horizon`
+    /// days. When `time_limit` milliseconds pass in the middle of a day, that
+    /// day goes on from the games expanded so far, which are the best ones.
+    pub fn plan(&self, game: &Game, board: &Board, time_limit: u128) -> DayPlan {
+        let start = Instant::now();
+        let score = |game: &Game| Simulation::get_score(game, board, true).value();
+        let roots = self.day_plans(game, board);
+        let mut beam: Vec<Node> = roots
+            .iter()
+            .enumerate()
+            .map(|(first, (_, game))| Node {
+                first,
+                game: *game,
+                score: score(game),
+            })
+            .collect();
+        self.keep_best(&mut beam);
+
+        let mut days = 1;
+        let mut out_of_time = false;
+        while !out_of_time && days < self.horizon && beam.iter().any(|n| !n.game.is_over()) {
+            let mut next = Vec::new();
+            let mut seen = HashSet::new();
+            for node in &beam {
+                if start.elapsed().as_millis() > time_limit {
+                    out_of_time = true;
+                    break;
+                }
+                if node.game.is_over() {
+                    if seen.insert(node.game.key()) {
+                        next.push(*node);
+                    }
+                    continue;
+                }
+                for (_, game) in self.day_plans(&node.game, board) {
+                    if seen.insert(game.key()) {
+                        next.push(Node {
+                            first: node.first,
+                            game,
+                            score: score(&game),
+                        });
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            self.keep_best(&mut next);
+            beam = next;
+            days += 1;
+        }
+
+        let best = beam[0];
+        DayPlan {
+            actions: roots[best.first].0.clone(),
+            days,
+            score: best.score,
+        }
+    }
+
+    fn keep_best(&self, nodes: &mut Vec<Node>) {
+        nodes.sort_by_key(|n| Reverse(n.score));
+        nodes.truncate(self.width.max(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day_plans_skip_permutations() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "5", "20", "20 0", "0 0 0", "4", "20 1 1 0", "24 1 1 0", "29 1 0 0", "33 1 0 0",
+        ]);
+        let plans = DayPlanner::new().day_plans(&game, &board);
+
+        let keys: HashSet<u64> = plans.iter().map(|(_, g)| g.key()).collect();
+        assert_eq!(keys.len(), plans.len());
+        assert!(plans
+            .iter()
+            .all(|(p, g)| p.last() == Some(&Action::WAIT) && g.day == 6));
+        let both_grown = plans
+            .iter()
+            .filter(|(p, _)| p.len() == 3)
+            .filter(|(p, _)| p.contains(&Action::GROW(20)) && p.contains(&Action::GROW(24)))
+            .count();
+        assert_eq!(both_grown, 1);
+    }
+
+    #[test]
+    fn test_plan_looks_days_ahead() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "5", "20", "20 0", "0 0 0", "4", "20 1 1 0", "24 1 1 0", "29 1 0 0", "33 1 0 0",
+        ]);
+        let plan = DayPlanner::default().plan(&game, &board, 100);
+
+        assert!(plan.days > 1);
+        assert_eq!(plan.actions.last(), Some(&Action::WAIT));
+        assert!(plan.actions[0].validate(&game, &board, true).is_ok());
+
+        let plan = DayPlanner::default().plan(&game, &board, 0);
+        assert!(plan.days >= 1);
+        assert!(plan.actions[0].validate(&game, &board, true).is_ok());
+    }
+}
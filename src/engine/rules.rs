@@ -0,0 +1,112 @@
+/// Parameters of the game that differ between the leagues. Everything else
+/// (board, sun movement, seed collisions) is the same in all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rules {
+    pub max_days: u8,
+    /// Base cost of growing a tree of size 0, 1 and 2.
+    pub grow_costs: [u8; 3],
+    pub complete_cost: u8,
+    pub starting_nutrients: u16,
+    /// Bonus points for completing a tree on a cell of richness 1, 2 and 3.
+    pub richness_bonus: [u16; 3],
+    pub starting_tree_size: u8,
+    pub seeds_enabled: bool,
+    pub grow_enabled: bool,
+    pub shadows_enabled: bool,
+}
+
+pub const LEAGUE_NAMES: [&str; 3] = ["wood2", "wood1", "bronze"];
+
+impl Rules {
+    /// The full game, used from Bronze league up.
+    pub const fn bronze() -> Self {
+        Self {
+            max_days: 24,
+            grow_costs: [1, 3, 7],
+            complete_cost: 4,
+            starting_nutrients: 20,
+            richness_bonus: [0, 2, 4],
+            starting_tree_size: 1,
+            seeds_enabled: true,
+            grow_enabled: true,
+            shadows_enabled: true,
+        }
+    }
+
+    /// A single day with grown trees where the only thing to do is COMPLETE.
+    pub const fn wood_2() -> Self {
+        Self {
+            max_days: 1,
+            starting_tree_size: 3,
+            seeds_enabled: false,
+            grow_enabled: false,
+            shadows_enabled: false,
+            ..Self::bronze()
+        }
+    }
+
+    /// Six days of GROW and COMPLETE without seeds and shadows.
+    pub const fn wood_1() -> Self {
+        Self {
+            max_days: 6,
+            seeds_enabled: false,
+            shadows_enabled: false,
+            ..Self::bronze()
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "wood2" => Some(Self::wood_2()),
+            "wood1" => Some(Self::wood_1()),
+            "bronze" => Some(Self::bronze()),
+            _ => None,
+        }
+    }
+
+    pub fn grow_cost(&self, size: u8) -> u8 {
+        match size {
+            0..=2 => self.grow_costs[size as usize],
+            _ => panic!("Can't grow a tree of size {}", size),
+        }
+    }
+
+    pub fn richness_bonus(&self, richness: u8) -> u16 {
+        match richness {
+            1..=3 => self.richness_bonus[richness as usize - 1],
+            _ => panic!(
+                "richness of a tree can only be 1, 2 and 3. Got: {}",
+                richness
+            ),
+        }
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::bronze()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leagues_by_name() {
+        for name in LEAGUE_NAMES.iter() {
+            assert!(Rules::by_name(name).is_some());
+        }
+        assert_eq!(Rules::by_name("bronze"), Some(Rules::default()));
+        assert_eq!(Rules::by_name("legend"), None);
+    }
+
+    #[test]
+    fn test_richness_bonus() {
+        let rules = Rules::bronze();
+        assert_eq!(
+            (1..4).map(|r| rules.richness_bonus(r)).collect::<Vec<_>>(),
+            vec![0, 2, 4]
+        );
+    }
+}
@@ -1,39 +1,363 @@
 use core::f64;
 use std::{
     cmp::{Ordering, Reverse},
-    collections::HashMap,
     f64::consts::SQRT_2,
+    fmt::Display,
+    time::{Duration, Instant},
     u32, usize,
 };
 
 use itertools::Itertools;
 use rand::prelude::SliceRandom;
 
-use super::{actions::Action, board::Board, common::random_max, game::Game};
+use super::{
+    actions::Action,
+    board::Board,
+    common::random_max,
+    game::{Game, GameResult},
+};
 pub struct Simulation<'a> {
     board: &'a Board,
     player_nodes: Vec<PlayerNode>,
     enemy_nodes: Vec<EnemyNode>,
     states: Vec<State>,
-    state_by_games: HashMap<&'a Game, u32>,
+    transpositions: TranspositionTable,
     current_state: u32,
+    max_states: usize,
+    exploration: f64,
+}
+
+/// How the action to play is picked among the children of the root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootSelection {
+    /// The most visited action, ties broken by the average score.
+    MostPicks,
+    BestAverage,
+}
+
+/// What a time budgeted search did, to print after every turn.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchStats {
+    pub iterations: u32,
+    /// Nodes of states in the whole tree, including kept ones.
+    pub states: usize,
+    /// The most turns a playout went below the root.
+    pub max_depth: u32,
+    pub elapsed_ms: u128,
+    pub best_action: Option<Action>,
+}
+
+impl Display for SearchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "iterations: {}, states: {}, depth: {}, elapsed: {} ms",
+            self.iterations, self.states, self.max_depth, self.elapsed_ms
+        )
+    }
+}
+
+/// Buckets in the transposition table of a new simulation.
+pub const TRANSPOSITION_BUCKETS: usize = 1 << 18;
+
+/// States a new simulation keeps at most. Action nodes are only added to
+/// kept states, so they are bounded too.
+pub const MAX_STATES: usize = 1_000_000;
+
+/// Exploration constant of UCB in a new simulation. Scores are differences
+/// of points, so it is larger than the usual √2.
+pub const UCB_CONSTANT: f64 = 20.0 * SQRT_2;
+
+/// Finds the state node of a game by its Zobrist key, so that a state reached
+/// by another order of moves is shared instead of expanded again. The table
+/// has a fixed number of buckets of two entries. When both are taken, the
+/// entry of the state with fewer visits is replaced; the replaced state stays
+/// in the tree, it just can't be found by later transpositions. The tree
+/// itself is bounded by the `max_states` of the simulation.
+struct TranspositionTable {
+    buckets: Vec<[Option<(u64, u32)>; 2]>,
+}
+
+impl TranspositionTable {
+    fn new(buckets: usize) -> Self {
+        Self {
+            buckets: vec![[None; 2]; buckets.max(1)],
+        }
+    }
+
+    fn bucket(&self, key: u64) -> usize {
+        (key % self.buckets.len() as u64) as usize
+    }
+
+    fn get(&self, key: u64) -> Option<u32> {
+        self.buckets[self.bucket(key)]
+            .iter()
+            .flatten()
+            .find(|(k, _)| *k == key)
+            .map(|(_, state_id)| *state_id)
+    }
+
+    fn insert(&mut self, key: u64, state_id: u32, picks: impl Fn(u32) -> u32) {
+        let bucket = self.bucket(key);
+        let entries = &mut self.buckets[bucket];
+        let slot = match entries.iter().position(|e| match e {
+            Some((k, _)) => *k == key,
+            None => true,
+        }) {
+            Some(slot) => slot,
+            None => (0..2)
+                .min_by_key(|i| picks(entries[*i].unwrap().1))
+                .unwrap(),
+        };
+        entries[slot] = Some((key, state_id));
+    }
+
+    fn len(&self) -> usize {
+        self.buckets.iter().flatten().flatten().count()
+    }
+
+    fn clear(&mut self) {
+        self.buckets.iter_mut().for_each(|b| *b = [None; 2]);
+    }
+}
+
+/// The nodes of a simulation without the board, so that a strategy can keep
+/// them from one turn to the next.
+pub struct SearchTree {
+    player_nodes: Vec<PlayerNode>,
+    enemy_nodes: Vec<EnemyNode>,
+    states: Vec<State>,
+    transpositions: TranspositionTable,
+    current_state: u32,
+    max_states: usize,
+    exploration: f64,
+}
+
+/// Keeps the search tree between turns. At the start of a turn the state
+/// reached by our last action and the game read from the input becomes the
+/// root, so the playouts of the previous turns below it are not lost.
+pub struct PersistentSearch {
+    tree: Option<SearchTree>,
+    last_action: Option<Action>,
+}
+
+impl Default for PersistentSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PersistentSearch {
+    pub fn new() -> Self {
+        Self {
+            tree: None,
+            last_action: None,
+        }
+    }
+
+    /// The simulation to search this turn: the kept tree moved to `game`, or
+    /// a new one when it's the first turn or the game was not in the tree.
+    pub fn start<'a>(&mut self, board: &'a Board, game: &Game) -> Simulation<'a> {
+        match (self.tree.take(), self.last_action.take()) {
+            (Some(tree), Some(action)) => {
+                let mut sim = Simulation::attach(board, tree);
+                if !sim.advance(action, game) {
+                    sim.reset(*game);
+                }
+                sim
+            }
+            _ => Simulation::new(board, *game),
+        }
+    }
+
+    /// Keeps the tree of `sim` for the next turn, where `action` was played.
+    pub fn finish(&mut self, sim: Simulation, action: Action) {
+        self.tree = Some(sim.detach());
+        self.last_action = Some(action);
+    }
 }
 
 impl<'a> Simulation<'a> {
     pub fn new(board: &'a Board, game: Game) -> Self {
+        Self::with_transpositions(board, game, TRANSPOSITION_BUCKETS)
+    }
+
+    /// A simulation whose transposition table has `buckets` buckets of two
+    /// states each.
+    pub fn with_transpositions(board: &'a Board, game: Game, buckets: usize) -> Self {
         let mut result = Self {
             current_state: 0,
             board: board,
-            states: Vec::with_capacity(1_000_000),
-            state_by_games: HashMap::new(),
-            player_nodes: Vec::with_capacity(1_000_000),
-            enemy_nodes: Vec::with_capacity(1_000_000),
+            states: Vec::with_capacity(MAX_STATES),
+            transpositions: TranspositionTable::new(buckets),
+            player_nodes: Vec::with_capacity(MAX_STATES),
+            enemy_nodes: Vec::with_capacity(MAX_STATES),
+            max_states: MAX_STATES,
+            exploration: UCB_CONSTANT,
         };
         result.current_state = result.create_state(game, None).0;
-        result.states[0].picks = 0;
+        result.transpositions.insert(game.key(), 0, |_| 0);
         return result;
     }
 
+    pub fn detach(self) -> SearchTree {
+        SearchTree {
+            player_nodes: self.player_nodes,
+            enemy_nodes: self.enemy_nodes,
+            states: self.states,
+            transpositions: self.transpositions,
+            current_state: self.current_state,
+            max_states: self.max_states,
+            exploration: self.exploration,
+        }
+    }
+
+    pub fn attach(board: &'a Board, tree: SearchTree) -> Self {
+        Self {
+            board,
+            player_nodes: tree.player_nodes,
+            enemy_nodes: tree.enemy_nodes,
+            states: tree.states,
+            transpositions: tree.transpositions,
+            current_state: tree.current_state,
+            max_states: tree.max_states,
+            exploration: tree.exploration,
+        }
+    }
+
+    /// Drops all nodes and starts again from `game`, keeping the memory.
+    pub fn reset(&mut self, game: Game) {
+        self.states.clear();
+        self.player_nodes.clear();
+        self.enemy_nodes.clear();
+        self.transpositions.clear();
+        self.current_state = self.create_state(game, None).0;
+        self.transpositions.insert(game.key(), 0, |_| 0);
+    }
+
+    /// Moves the root to the state after our `action` and an enemy action
+    /// that led to `game`, or else to any state of the tree equal to `game`.
+    /// Returns false when there is no such state and the tree is unchanged.
+    pub fn advance(&mut self, action: Action, game: &Game) -> bool {
+        let root = State::get_node(self.current_state, self);
+        let after_action = root
+            .children()
+            .map(|p| PlayerNode::get_node(*p, self))
+            .filter(|p| p.action == action)
+            .flat_map(|p| p.children())
+            .filter_map(|e| EnemyNode::get_node(*e, self).next_state)
+            .find(|s| self.states[*s as usize].game == *game);
+
+        let found = after_action.or_else(|| {
+            self.transpositions
+                .get(game.key())
+                .filter(|s| self.states[*s as usize].game == *game)
+        });
+        match found {
+            Some(state_id) => {
+                self.reroot(state_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Makes `state_id` the root and drops every node that is not below it.
+    /// The kept nodes are renumbered in breadth first order with their
+    /// statistics, and the transposition table is filled again.
+    fn reroot(&mut self, state_id: u32) {
+        const NONE: u32 = u32::MAX;
+        let mut new_states = vec![NONE; self.states.len()];
+        let mut order = vec![state_id];
+        new_states[state_id as usize] = 0;
+        let mut i = 0;
+        while i < order.len() {
+            for p in &self.states[order[i] as usize].child_nodes {
+                for e in &self.player_nodes[*p as usize].enemy_moves {
+                    if let Some(next) = self.enemy_nodes[*e as usize].next_state {
+                        if new_states[next as usize] == NONE {
+                            new_states[next as usize] = order.len() as u32;
+                            order.push(next);
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        fn take<T>(v: &mut Vec<T>) -> Vec<T> {
+            std::mem::replace(v, Vec::with_capacity(v.capacity()))
+        }
+        let mut old_states = take(&mut self.states).into_iter().map(Some).collect_vec();
+        let mut old_players = take(&mut self.player_nodes)
+            .into_iter()
+            .map(Some)
+            .collect_vec();
+        let mut old_enemies = take(&mut self.enemy_nodes)
+            .into_iter()
+            .map(Some)
+            .collect_vec();
+        let mut new_enemies = vec![NONE; old_enemies.len()];
+
+        for old_id in order {
+            let new_id = self.states.len() as u32;
+            let mut state = old_states[old_id as usize].take().unwrap();
+            for p in state.child_nodes.iter_mut() {
+                let mut player = old_players[*p as usize].take().unwrap();
+                *p = self.player_nodes.len() as u32;
+                player.parent_state = new_id;
+                for e in player.enemy_moves.iter_mut() {
+                    let mut enemy = old_enemies[*e as usize].take().unwrap();
+                    new_enemies[*e as usize] = self.enemy_nodes.len() as u32;
+                    *e = self.enemy_nodes.len() as u32;
+                    enemy.parent_action = *p;
+                    enemy.parent_state = new_id;
+                    enemy.next_state = enemy.next_state.map(|s| new_states[s as usize]);
+                    self.enemy_nodes.push(enemy);
+                }
+                self.player_nodes.push(player);
+            }
+            self.states.push(state);
+        }
+
+        for state in self.states.iter_mut() {
+            state.parents = state
+                .parents
+                .iter()
+                .map(|e| new_enemies[*e as usize])
+                .filter(|e| *e != NONE)
+                .collect();
+        }
+
+        let states = &self.states;
+        self.transpositions.clear();
+        for (id, state) in states.iter().enumerate() {
+            self.transpositions
+                .insert(state.game.key(), id as u32, |s| states[s as usize].picks);
+        }
+        self.current_state = 0;
+    }
+
+    /// Number of states that can be found by their key.
+    pub fn transpositions_len(&self) -> usize {
+        self.transpositions.len()
+    }
+
+    pub fn states_len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Once the tree has `max_states` states, playouts go on below it without
+    /// adding nodes.
+    pub fn set_max_states(&mut self, max_states: usize) {
+        self.max_states = max_states.max(1);
+    }
+
+    /// The constant `c` of UCB, higher explores more.
+    pub fn set_exploration(&mut self, c: f64) {
+        self.exploration = c;
+    }
+
     pub fn print_simulation(sim: &Simulation, state_id: u32, level: usize, max_level: usize) {
         fn indent(level: usize) -> String {
             let mut result = String::new();
@@ -110,7 +434,7 @@ impl<'a> Simulation<'a> {
             game: game,
             picks: 0,
             total_score: 0,
-            parent: parent,
+            parents: parent.into_iter().collect(),
             max_score: i32::MIN,
         };
 
@@ -130,13 +454,17 @@ impl<'a> Simulation<'a> {
     }
 
     fn action_weight(&self, state_id: u32, action: &Action) -> u32 {
-        let state = State::get_node(state_id, self);
-        let day = state.game.day;
-        let board = self.board;
+        Self::weight_of(&State::get_node(state_id, self).game, self.board, action)
+    }
+
+    /// How much `action` is worth trying in `game`, by a few rules of thumb.
+    /// Zero for actions not worth trying at all.
+    pub fn weight_of(game: &Game, board: &Board, action: &Action) -> u32 {
+        let day = game.day;
 
         match *action {
             Action::COMPLETE(x) => 5,
-            Action::GROW(x) if day < 20 => state.game.trees().get(x).size() as u32 + 1,
+            Action::GROW(x) if day < 20 => game.trees().get(x).size() as u32 + 1,
             Action::SEED(_, to) if day < 18 && board.get_richness(to) == 3 => 4,
             Action::WAIT if day <= 1 => 2,
             Action::SEED(_, _) if day > 18 => 0,
@@ -160,28 +488,41 @@ impl<'a> Simulation<'a> {
         game
     }
 
-    fn get_or_create_next_state(&mut self, enemy_id: u32) -> u32 {
+    /// None when the state is new and the tree is full.
+    fn get_or_create_next_state(&mut self, enemy_id: u32) -> Option<u32> {
         let ref enemy_node = self.enemy_nodes[enemy_id as usize];
 
         if let Some(next_state) = enemy_node.next_state {
-            return next_state;
+            return Some(next_state);
         }
         let new_game = Self::create_next_game(self, &enemy_node);
-        let (state_id, _) = self.create_state(new_game, Some(enemy_id));
+        let key = new_game.key();
+
+        let state_id = match self.transpositions.get(key) {
+            Some(id) if self.states[id as usize].game == new_game => {
+                self.states[id as usize].parents.push(enemy_id);
+                id
+            }
+            _ if self.states.len() >= self.max_states => return None,
+            _ => {
+                let (id, _) = self.create_state(new_game, Some(enemy_id));
+                let states = &self.states;
+                self.transpositions
+                    .insert(key, id, |s| states[s as usize].picks);
+                id
+            }
+        };
 
         self.enemy_nodes[enemy_id as usize].next_state = Some(state_id);
-        state_id
+        Some(state_id)
     }
 
-    pub fn simulate_current(&mut self, cache: &mut HashMap<Game, u32>) {
-        self.simulate(self.current_state, cache)
+    pub fn simulate_current(&mut self) {
+        self.simulate(self.current_state)
     }
 
-    fn cache_state(&self, cache: &mut HashMap<Game, u32>, state_id: u32) {
-        let game = &self.states[state_id as usize].game;
-        if !cache.contains_key(game) {
-            cache.insert(game.clone(), state_id);
-        }
+    pub fn current_state(&self) -> u32 {
+        self.current_state
     }
 
     pub fn set_current(&mut self, state: u32) {
@@ -334,7 +675,7 @@ impl<'a> Simulation<'a> {
         fn get_points_for_tree(game: &Game, size: u8, is_player: bool) -> i32 {
             let max_trees = vec![2, 3, 4, 5];
             let amount = game.trees().get_amount_of_size(size, is_player) as i32;
-            let days_remaining = 24 - game.day;
+            let days_remaining = game.rules().max_days - game.day;
             let can_complete = 4 - size <= days_remaining;
             let within_max = amount <= max_trees[size as usize];
             return match (can_complete, within_max) {
@@ -355,8 +696,8 @@ impl<'a> Simulation<'a> {
         let player_income = game.average_sun_income(board, is_player) as i32;
         let enemy_income = game.average_sun_income(board, !is_player) as i32;
 
-        let mut sun_budget =
-            game.get_sun_points(is_player) as i32 + player_income * (23 - game.day as i32);
+        let mut sun_budget = game.get_sun_points(is_player) as i32
+            + player_income * (game.rules().max_days as i32 - 1 - game.day as i32);
 
         let mut potential_harvest = 0;
 
@@ -374,12 +715,16 @@ impl<'a> Simulation<'a> {
                 n -= 1;
             }
         }
-        let points = game.get_points(is_player) as i32;
-
-        let win_score = match (game.day >= 23, game.is_player_won()) {
-            (true, x) if x == is_player => 50,
-            (true, _) => -50,
-            (false, _) => 0,
+        let (points, win_score) = if game.is_over() {
+            let result = game.final_scores();
+            let win_score = match result.winner {
+                Some(x) if x == is_player => 50,
+                Some(_) => -50,
+                None => 0,
+            };
+            (result.score(is_player) as i32, win_score)
+        } else {
+            (game.get_points(is_player) as i32, 0)
         };
 
         return Score {
@@ -388,7 +733,7 @@ impl<'a> Simulation<'a> {
             richness_score: 0,
             sun_score: 3 * player_income - 5 * enemy_income,
             trees_score: potential_harvest,
-            win_score: 0,
+            win_score,
         };
     }
 
@@ -403,7 +748,7 @@ impl<'a> Simulation<'a> {
 
         let nutrients = game.nutrients as i32;
         let day = game.day;
-        let days_remaining = 24 - game.day;
+        let days_remaining = game.rules().max_days - game.day;
 
         let my_trees_0 = game.trees().get_amount_of_size(0, is_player) as i32;
         let my_trees_1 = game.trees().get_amount_of_size(1, is_player) as i32;
@@ -431,10 +776,10 @@ impl<'a> Simulation<'a> {
 
         let score_for_richness = total_richness_by_trees * 4;
 
-        let win_score = match (game.day >= 23, game.is_player_won()) {
-            (true, x) if x == is_player => 100,
-            (true, _) => -100,
-            (false, _) => 0,
+        let win_score = match (game.is_over(), game.final_scores().winner) {
+            (true, Some(x)) if x == is_player => 100,
+            (true, Some(_)) => -100,
+            (_, _) => 0,
         };
 
         return Score::new(
@@ -455,73 +800,159 @@ impl<'a> Simulation<'a> {
         todo!()
     }
 
-    fn propagate_score(&mut self, state_id: u32, player_score: i32, enemy_score: i32) {
-        let mut current_node_id = state_id;
+    /// Adds the scores to every node on `path`, the enemy nodes picked from
+    /// the start state down to `leaf`, which is None when the playout left the
+    /// tree. A state reached by several paths is updated once per visit, while
+    /// the action nodes only count the visits that went through them.
+    fn propagate_score(
+        &mut self,
+        path: &[u32],
+        leaf: Option<u32>,
+        player_score: i32,
+        enemy_score: i32,
+    ) {
+        fn add(total: &mut i32, max: &mut i32, picks: &mut u32, score: i32) {
+            *picks += 1;
+            *total += score;
+            *max = std::cmp::max(*max, score);
+        }
+        let diff = player_score - enemy_score;
+
+        if let Some(state_id) = leaf {
+            let state = &mut self.states[state_id as usize];
+            add(
+                &mut state.total_score,
+                &mut state.max_score,
+                &mut state.picks,
+                diff,
+            );
+        }
+        for enemy_id in path.iter().rev() {
+            let enemy = &mut self.enemy_nodes[*enemy_id as usize];
+            add(
+                &mut enemy.total_score,
+                &mut enemy.max_score,
+                &mut enemy.picks,
+                -diff,
+            );
+            let (player_id, state_id) = (enemy.parent_action, enemy.parent_state);
+
+            let player = &mut self.player_nodes[player_id as usize];
+            add(
+                &mut player.total_score,
+                &mut player.max_score,
+                &mut player.picks,
+                diff,
+            );
+
+            let state = &mut self.states[state_id as usize];
+            add(
+                &mut state.total_score,
+                &mut state.max_score,
+                &mut state.picks,
+                diff,
+            );
+        }
+    }
+
+    /// Runs playouts from `state` until `time_budget` milliseconds have
+    /// passed, at least one. The clock is read every few playouts only.
+    pub fn simulate3(
+        &mut self,
+        state: u32,
+        depth: u32,
+        width: usize,
+        time_budget: u32,
+    ) -> SearchStats {
+        const CHECK_EVERY: u32 = 8;
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(time_budget as u64);
+        let mut stats = SearchStats::default();
         loop {
-            if current_node_id == self.current_state {
+            let reached = self.iterate(state, depth, width);
+            stats.iterations += 1;
+            stats.max_depth = stats.max_depth.max(reached);
+            if stats.iterations % CHECK_EVERY == 0 && Instant::now() >= deadline {
                 break;
             }
-            let state_node = &mut self.states[current_node_id as usize];
-            state_node.picks += 1;
-            state_node.total_score += player_score - enemy_score;
-            state_node.max_score = std::cmp::max(state_node.max_score, player_score - enemy_score);
-
-            let (enemy_id, _) = State::get_node(current_node_id, self).get_parent(self);
-            let enemy_node = &mut self.enemy_nodes[enemy_id as usize];
-            enemy_node.max_score = std::cmp::max(enemy_node.max_score, enemy_score - player_score);
-            enemy_node.total_score += enemy_score - player_score;
-            enemy_node.picks += 1;
-
-            let (player_id, _) = EnemyNode::get_node(enemy_id, self).get_parent(self);
-            let player_node = &mut self.player_nodes[player_id as usize];
-            player_node.max_score =
-                std::cmp::max(player_node.max_score, player_score - enemy_score);
-            player_node.total_score += player_score - enemy_score;
-            player_node.picks += 1;
-
-            let (next_state_id, _) = PlayerNode::get_node(player_id, self).get_parent(self);
-            current_node_id = next_state_id;
         }
+        stats.states = self.states.len();
+        stats.elapsed_ms = start.elapsed().as_millis();
+        stats.best_action = self.best_action(state, RootSelection::MostPicks);
+        stats
     }
 
-    pub fn simulate3(&mut self, state: u32, depth: u32, width: usize, time_budget: u32) {}
-
     pub fn simulate2(&mut self, state: u32, depth: u32, width: usize, iterations: u32) {
-        //self.cache_state(cache, state);
         for _ in 0..iterations {
-            let mut state_id = state;
-            for d in 0..depth {
-                self.ensure_player_best_nodes(state_id, width);
-                let state = State::get_node(state_id, self);
-
-                let (p_id, _) = Self::pick_node_by_ucb_2(&self, state);
-                self.ensure_enemy_best_nodes(state_id, p_id, width);
-                let (enemy_id, _) =
-                    Self::pick_node_by_ucb_2(&self, &self.player_nodes[p_id as usize]);
-                let next_state_id = self.get_or_create_next_state(enemy_id);
-                let next_state = State::get_node(next_state_id, self);
-                let ref next_game = next_state.game;
-                //self.cache_state(cache, next_state_id);
-                state_id = next_state_id; //d
-
-                if next_game.day == 24 {
+            self.iterate(state, depth, width);
+        }
+    }
+
+    /// One playout of at most `depth` turns, scored with `get_score` at the
+    /// end. When the tree is full it ends at the first game not in the tree.
+    /// Returns the number of turns played.
+    fn iterate(&mut self, state: u32, depth: u32, width: usize) -> u32 {
+        let mut state_id = state;
+        let mut path = Vec::with_capacity(depth as usize);
+        let mut off_tree = None;
+        for _ in 0..depth {
+            self.ensure_player_best_nodes(state_id, width);
+            let state = State::get_node(state_id, self);
+
+            let (p_id, _) = Self::pick_node_by_ucb_2(self, state);
+            self.ensure_enemy_best_nodes(state_id, p_id, width);
+            let (enemy_id, _) = Self::pick_node_by_ucb_2(self, &self.player_nodes[p_id as usize]);
+            path.push(enemy_id);
+            match self.get_or_create_next_state(enemy_id) {
+                Some(next) => state_id = next,
+                None => {
+                    off_tree = Some(Self::create_next_game(
+                        self,
+                        &self.enemy_nodes[enemy_id as usize],
+                    ));
                     break;
-                    //let player_won = next_game.is_player_won();
-                    //self.on_player_won(next_state_id, player_won);
-                    //break;
                 }
             }
 
-            let game = &self.states[state_id as usize].game;
-            let player_score = Self::get_score(game, &self.board, true).value();
-            let enemy_score = Self::get_score(game, &self.board, false).value();
-            self.propagate_score(state_id, player_score, enemy_score);
+            if self.states[state_id as usize].game.is_over() {
+                break;
+            }
         }
+
+        let leaf = match off_tree {
+            Some(_) => None,
+            None => Some(state_id),
+        };
+        let game = off_tree.unwrap_or(self.states[state_id as usize].game);
+        let player_score = Self::get_score(&game, self.board, true).value();
+        let enemy_score = Self::get_score(&game, self.board, false).value();
+        self.propagate_score(&path, leaf, player_score, enemy_score);
+        path.len() as u32
+    }
+
+    /// Our action to play in `state_id` after the search, None when nothing
+    /// was expanded there.
+    pub fn best_action(&self, state_id: u32, selection: RootSelection) -> Option<Action> {
+        let state = State::get_node(state_id, self);
+        let nodes = state.children().map(|p| PlayerNode::get_node(*p, self));
+        let best = match selection {
+            RootSelection::MostPicks => nodes.max_by(|x, y| {
+                (x.picks, x.avg_score())
+                    .partial_cmp(&(y.picks, y.avg_score()))
+                    .unwrap_or(Ordering::Equal)
+            }),
+            RootSelection::BestAverage => nodes.filter(|x| x.picks > 0).max_by(|x, y| {
+                x.avg_score()
+                    .partial_cmp(&y.avg_score())
+                    .unwrap_or(Ordering::Equal)
+            }),
+        };
+        best.map(|p| p.action)
     }
 
-    pub fn simulate(&mut self, state: u32, cache: &mut HashMap<Game, u32>) {
-        //self.cache_state(cache, state);
+    pub fn simulate(&mut self, state: u32) {
         let mut state_id = state;
+        let mut path = Vec::new();
         loop {
             self.ensure_player_nodes(state_id, 100);
             let state = State::get_node(state_id, self);
@@ -529,58 +960,79 @@ impl<'a> Simulation<'a> {
             let (p_id, _) = Self::pick_node_by_ucb_2(&self, state);
             self.ensure_enemy_nodes(state_id, p_id, 100);
             let (enemy_id, _) = Self::pick_node_by_ucb_2(&self, &self.player_nodes[p_id as usize]);
-            let next_state_id = self.get_or_create_next_state(enemy_id);
-            let next_state = State::get_node(next_state_id, self);
-            let ref next_game = next_state.game;
-            //self.cache_state(cache, next_state_id);
-
-            if next_game.day == 24 {
-                let player_won = next_game.is_player_won();
-                self.on_player_won(next_state_id, player_won);
+            path.push(enemy_id);
+            let next_state_id = match self.get_or_create_next_state(enemy_id) {
+                Some(id) => id,
+                None => {
+                    let game = Self::create_next_game(self, &self.enemy_nodes[enemy_id as usize]);
+                    let result = Self::play_randomly(game, self.board).final_scores();
+                    self.on_player_won(&path, result);
+                    break;
+                }
+            };
+            let next_game = &State::get_node(next_state_id, self).game;
+
+            if next_game.is_over() {
+                let result = next_game.final_scores();
+                self.on_player_won(&path, result);
                 break;
             }
             state_id = next_state_id;
         }
     }
 
-    pub fn mark_enemy(&mut self, enemy_id: u32, is_player_won: bool) {
-        let node = self.enemy_nodes.get_mut(enemy_id as usize).unwrap();
-        node.picks += 1;
-        if !is_player_won {
-            node.total_score += 1;
+    /// Plays random actions for both sides until the end of the game.
+    fn play_randomly(mut game: Game, board: &Board) -> Game {
+        let mut rng = rand::thread_rng();
+        while !game.is_over() {
+            let player = *Action::find_next_actions(&game, board, true)
+                .choose(&mut rng)
+                .unwrap();
+            let enemy = *Action::find_next_actions(&game, board, false)
+                .choose(&mut rng)
+                .unwrap();
+            game = game.apply_actions(board, player, enemy);
         }
+        game
     }
 
-    pub fn mark_player(&mut self, player_id: u32, is_player_won: bool) {
-        let node = self.player_nodes.get_mut(player_id as usize).unwrap();
-        node.picks += 1;
-        if is_player_won {
-            node.total_score += 1;
+    /// A win is worth 2, a draw 1 and a loss 0, so that draws are not
+    /// counted as losses for both sides.
+    fn reward(result: GameResult, is_player: bool) -> i32 {
+        match result.winner {
+            Some(x) if x == is_player => 2,
+            Some(_) => 0,
+            None => 1,
         }
     }
 
-    pub fn mark_state(&mut self, state_id: u32, is_player_won: bool) {
-        let node = self.states.get_mut(state_id as usize).unwrap();
+    pub fn mark_enemy(&mut self, enemy_id: u32, result: GameResult) {
+        let node = self.enemy_nodes.get_mut(enemy_id as usize).unwrap();
         node.picks += 1;
+        node.total_score += Self::reward(result, false);
+    }
 
-        if is_player_won {
-            node.total_score += 1;
-        }
+    pub fn mark_player(&mut self, player_id: u32, result: GameResult) {
+        let node = self.player_nodes.get_mut(player_id as usize).unwrap();
+        node.picks += 1;
+        node.total_score += Self::reward(result, true);
     }
 
-    pub fn on_player_won(&mut self, node_id: u32, is_player_won: bool) {
-        let mut current_node_id = node_id;
-        loop {
-            if current_node_id == self.current_state {
-                break;
-            }
-            let (enemy_id, _) = State::get_node(current_node_id, self).get_parent(self);
-            self.mark_enemy(enemy_id, is_player_won);
-            let (player_id, _) = EnemyNode::get_node(enemy_id, self).get_parent(self);
-            self.mark_player(player_id, is_player_won);
-            let (next_state_id, _) = PlayerNode::get_node(player_id, self).get_parent(self);
-            self.mark_state(next_state_id, is_player_won);
-            current_node_id = next_state_id;
+    pub fn mark_state(&mut self, state_id: u32, result: GameResult) {
+        let node = self.states.get_mut(state_id as usize).unwrap();
+        node.picks += 1;
+        node.total_score += Self::reward(result, true);
+    }
+
+    /// Marks the result on every node of `path`, the enemy nodes picked on
+    /// the way to the end of the game.
+    pub fn on_player_won(&mut self, path: &[u32], result: GameResult) {
+        for enemy_id in path.iter().rev() {
+            self.mark_enemy(*enemy_id, result);
+            let enemy = &self.enemy_nodes[*enemy_id as usize];
+            let (player_id, state_id) = (enemy.parent_action, enemy.parent_state);
+            self.mark_player(player_id, result);
+            self.mark_state(state_id, result);
         }
     }
 }
@@ -589,7 +1041,8 @@ impl<'a> Simulation<'a> {
 pub struct State {
     game: Game,
     child_nodes: Vec<u32>,
-    parent: Option<u32>,
+    /// Enemy nodes leading to this state, more than one for transpositions.
+    parents: Vec<u32>,
     max_score: i32,
     total_score: i32,
     picks: u32,
@@ -632,7 +1085,7 @@ pub trait GameNode {
 
         let (_, parent) = self.get_parent(simulation);
         return self.avg_score()
-            + 20.0 * SQRT_2 * ((parent.picks() as f64).ln() / self.picks() as f64).sqrt();
+            + simulation.exploration * ((parent.picks() as f64).ln() / self.picks() as f64).sqrt();
     }
 }
 
@@ -723,8 +1176,8 @@ impl GameNode for State {
     }
 
     fn get_parent<'a>(&self, simulation: &'a Simulation) -> (u32, &'a Self::Parent) {
-        let ref node = simulation.enemy_nodes[self.parent.unwrap() as usize];
-        (self.parent.unwrap(), node)
+        let parent = self.parents[0];
+        (parent, &simulation.enemy_nodes[parent as usize])
     }
 
     fn total_score(&self) -> i32 {
@@ -835,7 +1288,7 @@ pub trait HasScore {
     fn value(self) -> i32;
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Score {
     area_score: i32,
     trees_score: i32,
@@ -869,6 +1322,22 @@ impl Score {
     }
 }
 
+impl Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "points {}, trees {}, sun {}, area {}, richness {}, win {} = {}",
+            self.points_score,
+            self.trees_score,
+            self.sun_score,
+            self.area_score,
+            self.richness_score,
+            self.win_score,
+            self.value()
+        )
+    }
+}
+
 impl HasScore for Score {
     fn value(self) -> i32 {
         self.value()
@@ -937,7 +1406,10 @@ mod tests {
         actions::Action,
         board::Board,
         game::Game,
-        simulation::{EnemyNode, GameNode, HasChildren, PlayerNode, Simulation, State},
+        simulation::{
+            EnemyNode, GameNode, HasChildren, PersistentSearch, PlayerNode, RootSelection,
+            Simulation, State,
+        },
         tree::{Tree, TreeCollection},
     };
 
@@ -948,8 +1420,7 @@ mod tests {
             "0", "20", "2 0", "2 0 0", "4", "24 1 1 0", "27 1 1 0", "33 1 0 0", "36 1 0 0",
         ]);
         let mut sim = Simulation::new(&board, game);
-        let mut cache = HashMap::new();
-        sim.simulate_current(&mut cache);
+        sim.simulate_current();
         assert_eq!(sim.current_state, 0);
     }
 
@@ -960,10 +1431,9 @@ mod tests {
         ]);
         let mut sim = Simulation::new(&board, game);
         let d = Instant::now();
-        let mut cache = HashMap::new();
 
         for _ in 0..10 {
-            sim.simulate_current(&mut cache);
+            sim.simulate_current();
         }
         println!("{} ms", Duration::as_millis(&d.elapsed()));
         let moves = sim
@@ -991,9 +1461,8 @@ mod tests {
             "0", "20", "2 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
         ]);
         let mut sim = Simulation::new(&board, game);
-        let mut cache = HashMap::new();
         for _ in 0..1000 {
-            sim.simulate_current(&mut cache);
+            sim.simulate_current();
         }
 
         let moves = sim
@@ -1169,6 +1638,207 @@ mod tests {
         print_score(&game, &board);
     }
 
+    fn play(sim: &mut Simulation, state_id: u32, action: Action) -> u32 {
+        let player_id = PlayerNode::create(state_id, sim, action, vec![Action::WAIT]);
+        let enemy_id = sim.player_nodes[player_id as usize].enemy_moves[0];
+        sim.get_or_create_next_state(enemy_id).unwrap()
+    }
+
+    #[test]
+    fn test_transpositions_share_state() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "20 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        let mut sim = Simulation::new(&board, game);
+
+        let grow_29 = play(&mut sim, 0, Action::GROW(29));
+        let grow_33 = play(&mut sim, 0, Action::GROW(33));
+        let both_1 = play(&mut sim, grow_29, Action::GROW(33));
+        let both_2 = play(&mut sim, grow_33, Action::GROW(29));
+
+        assert_eq!(both_1, both_2);
+        assert_eq!(sim.states[both_1 as usize].parents.len(), 2);
+        assert_eq!(sim.states_len(), 4);
+    }
+
+    #[test]
+    fn test_backup_counts_every_visit() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "20 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        let mut sim = Simulation::new(&board, game);
+        sim.simulate2(0, 3, 5, 200);
+
+        assert_eq!(sim.states[0].picks, 200);
+        for state in sim.states.iter().skip(1).filter(|s| s.picks > 0) {
+            let through_parents: u32 = state
+                .parents
+                .iter()
+                .map(|p| sim.enemy_nodes[*p as usize].picks)
+                .sum();
+            assert_eq!(state.picks, through_parents);
+        }
+    }
+
+    #[test]
+    fn test_transposition_table_is_bounded() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "20 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        let mut sim = Simulation::with_transpositions(&board, game, 4);
+        sim.simulate2(0, 3, 5, 100);
+
+        assert!(sim.states_len() > 8);
+        assert_eq!(sim.transpositions_len(), 8);
+    }
+
+    #[test]
+    fn test_states_are_capped() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "20 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        let mut sim = Simulation::new(&board, game);
+        sim.set_max_states(50);
+        let stats = sim.simulate3(0, 4, 10, 20);
+        for _ in 0..20 {
+            sim.simulate(0);
+        }
+
+        assert_eq!(sim.states_len(), 50);
+        let picks: u32 = sim.get_moves_summary().map(|p| p.picks).sum();
+        assert!(picks > stats.iterations);
+    }
+
+    #[test]
+    fn test_simulate_within_time_budget() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "20 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        let mut sim = Simulation::new(&board, game);
+        assert_eq!(sim.best_action(0, RootSelection::MostPicks), None);
+
+        let stats = sim.simulate3(0, 4, 10, 30);
+
+        assert!(stats.iterations >= 8);
+        assert!(stats.elapsed_ms >= 30);
+        assert_eq!(stats.iterations, sim.states[0].picks);
+        assert_eq!(stats.states, sim.states_len());
+        assert!(stats.max_depth > 0 && stats.max_depth <= 4);
+
+        let most_picked = sim.get_moves_summary().map(|p| p.picks).max();
+        let best = sim
+            .get_moves_summary()
+            .find(|p| Some(p.action) == stats.best_action);
+        assert_eq!(best.map(|p| p.picks), most_picked);
+        assert!(sim.best_action(0, RootSelection::BestAverage).is_some());
+    }
+
+    #[test]
+    fn test_advance_keeps_subtree() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "20 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        let mut sim = Simulation::new(&board, game);
+        sim.simulate2(0, 3, 5, 300);
+        let states_before = sim.states_len();
+
+        let enemy = sim
+            .enemy_nodes
+            .iter()
+            .filter(|e| e.parent_state == 0 && e.next_state.is_some())
+            .max_by_key(|e| e.picks)
+            .unwrap();
+        let action = sim.player_nodes[enemy.parent_action as usize].action;
+        let next = sim.states[enemy.next_state.unwrap() as usize].game;
+        let picks = sim.states[enemy.next_state.unwrap() as usize].picks;
+
+        assert!(sim.advance(action, &next));
+        assert_eq!(sim.current_state, 0);
+        assert_eq!(sim.states[0].game, next);
+        assert_eq!(sim.states[0].picks, picks);
+        assert!(sim.states[0].parents.is_empty());
+        assert!(sim.states_len() < states_before);
+
+        for (id, player) in sim.player_nodes.iter().enumerate() {
+            let state = &sim.states[player.parent_state as usize];
+            assert!(state.child_nodes.contains(&(id as u32)));
+            for e in &player.enemy_moves {
+                assert_eq!(sim.enemy_nodes[*e as usize].parent_action, id as u32);
+            }
+        }
+        for (id, enemy) in sim.enemy_nodes.iter().enumerate() {
+            if let Some(s) = enemy.next_state {
+                assert!(sim.states[s as usize].parents.contains(&(id as u32)));
+            }
+        }
+
+        sim.simulate2(0, 3, 5, 10);
+        assert_eq!(sim.states[0].picks, picks + 10);
+    }
+
+    #[test]
+    fn test_advance_to_unknown_game() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "20 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        let mut sim = Simulation::new(&board, game);
+        sim.simulate2(0, 2, 5, 50);
+        let states_before = sim.states_len();
+
+        let other = Game::parse_from_strings(vec![
+            "5", "20", "20 0", "2 0 0", "2", "20 1 0 0", "29 1 1 0",
+        ]);
+        assert!(!sim.advance(Action::WAIT, &other));
+        assert_eq!(sim.states_len(), states_before);
+    }
+
+    #[test]
+    fn test_persistent_search_reuses_tree() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "20 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        let mut search = PersistentSearch::new();
+
+        let mut sim = search.start(&board, &game);
+        sim.simulate2(0, 3, 100, 300);
+        search.finish(sim, Action::GROW(29));
+
+        let next = game.apply_actions(&board, Action::GROW(29), Action::WAIT);
+        let sim = search.start(&board, &next);
+        assert_eq!(sim.states[0].game, next);
+        assert!(sim.states[0].picks > 0);
+        search.finish(sim, Action::WAIT);
+
+        let sim = search.start(&board, &game);
+        assert_eq!(sim.states_len(), 1);
+        assert_eq!(sim.states[0].picks, 0);
+    }
+
+    #[test]
+    fn test_exploration_is_tunable() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "20 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        let mut sim = Simulation::new(&board, game);
+        sim.simulate2(0, 3, 10, 50);
+        let node = sim.get_moves_summary().find(|p| p.picks > 0).unwrap();
+        assert!(node.ucb(&sim) > node.avg_score());
+
+        sim.set_exploration(0.0);
+        let sim = Simulation::attach(&board, sim.detach());
+        let node = sim.get_moves_summary().find(|p| p.picks > 0).unwrap();
+        assert_eq!(node.ucb(&sim), node.avg_score());
+    }
+
     #[test]
     fn test_win_loose() {
         let board = Board::default_with_inactive(vec![].into_iter());
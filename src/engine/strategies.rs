@@ -0,0 +1,300 @@
+use itertools::Itertools;
+use rand::prelude::SliceRandom;
+
+use super::{
+    actions::Action,
+    board::Board,
+    game::{get_next_action_wood, search_next_action_against, Game, PathStep, SearchSettings},
+    game_loop::Strategy,
+    opponent::{Aggregation, BestResponses, OpponentModel, WaitingOpponent},
+    parallel::ParallelSearch,
+    planner::DayPlanner,
+    simulation::{PersistentSearch, RootSelection, UCB_CONSTANT},
+};
+
+/// Beam search of our actions, against an enemy that waits by default. The
+/// rest of the best path is kept and searched on from in the next turn when
+/// the game went as planned.
+pub struct BeamSearch {
+    opponent: Box<dyn OpponentModel>,
+    aggregation: Aggregation,
+    plan: Vec<PathStep>,
+}
+
+impl Default for BeamSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BeamSearch {
+    pub fn new() -> BeamSearch {
+        BeamSearch {
+            opponent: Box::new(WaitingOpponent),
+            aggregation: Aggregation::WorstCase,
+            plan: Vec::new(),
+        }
+    }
+
+    pub fn with_opponent(
+        mut self,
+        opponent: Box<dyn OpponentModel>,
+        aggregation: Aggregation,
+    ) -> BeamSearch {
+        self.opponent = opponent;
+        self.aggregation = aggregation;
+        self
+    }
+}
+
+impl Strategy for BeamSearch {
+    fn on_start(&mut self, _board: &Board) {}
+
+    fn get_next(&mut self, game: &Game, board: &Board, time_limit: u128) -> Action {
+        let continued = match self.plan.first() {
+            Some(step) if step.game == *game => &self.plan[1..],
+            _ => &[],
+        };
+        let settings = SearchSettings::new(5, time_limit)
+            .with_opponent(self.opponent.as_ref(), self.aggregation)
+            .with_plan(continued);
+        let result = search_next_action_against(game, board, settings);
+        eprintln!("beam continued {} steps, {}", continued.len(), result);
+        self.plan = result.path.clone();
+        result.action()
+    }
+}
+
+/// Plans whole days ahead with `DayPlanner` and plays the first action of the
+/// plan. The plan is made again every turn.
+pub struct DayPlanStrategy {
+    planner: DayPlanner,
+}
+
+impl Default for DayPlanStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DayPlanStrategy {
+    pub fn new() -> DayPlanStrategy {
+        DayPlanStrategy {
+            planner: DayPlanner::new(),
+        }
+    }
+}
+
+impl Strategy for DayPlanStrategy {
+    fn on_start(&mut self, _board: &Board) {}
+
+    fn get_next(&mut self, game: &Game, board: &Board, time_limit: u128) -> Action {
+        let plan = self.planner.plan(game, board, time_limit * 3 / 4);
+        let action = plan.actions[0];
+        eprintln!(
+            "days: {}, score: {}, plan: {}",
+            plan.days,
+            plan.score,
+            plan.actions.iter().join(" ")
+        );
+        action
+    }
+}
+
+/// Picks the best action by the `compare` heuristic without looking ahead.
+pub struct WoodStrategy {}
+
+impl Default for WoodStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WoodStrategy {
+    pub fn new() -> WoodStrategy {
+        WoodStrategy {}
+    }
+}
+
+impl Strategy for WoodStrategy {
+    fn on_start(&mut self, _board: &Board) {}
+
+    fn get_next(&mut self, game: &Game, board: &Board, _time_limit: u128) -> Action {
+        get_next_action_wood(game, board, &Action::find_next_actions(game, board, true))
+    }
+}
+
+/// Plays a uniformly random legal action.
+pub struct RandomStrategy {}
+
+impl Default for RandomStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RandomStrategy {
+    pub fn new() -> RandomStrategy {
+        RandomStrategy {}
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn on_start(&mut self, _board: &Board) {}
+
+    fn get_next(&mut self, game: &Game, board: &Board, _time_limit: u128) -> Action {
+        *Action::find_next_actions(game, board, true)
+            .choose(&mut rand::thread_rng())
+            .unwrap()
+    }
+}
+
+/// Tree search with `Simulation` for most of the time limit. The tree is kept
+/// between turns, so the search goes on where the last turn stopped.
+pub struct MctsStrategy {
+    search: PersistentSearch,
+    depth: u32,
+    width: usize,
+    selection: RootSelection,
+    exploration: f64,
+}
+
+impl Default for MctsStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MctsStrategy {
+    pub fn new() -> MctsStrategy {
+        MctsStrategy {
+            search: PersistentSearch::new(),
+            depth: 6,
+            width: 10,
+            selection: RootSelection::MostPicks,
+            exploration: UCB_CONSTANT,
+        }
+    }
+
+    pub fn with_selection(mut self, selection: RootSelection) -> MctsStrategy {
+        self.selection = selection;
+        self
+    }
+
+    /// The constant `c` of UCB, `UCB_CONSTANT` by default.
+    pub fn with_exploration(mut self, c: f64) -> MctsStrategy {
+        self.exploration = c;
+        self
+    }
+}
+
+impl Strategy for MctsStrategy {
+    fn on_start(&mut self, _board: &Board) {}
+
+    fn get_next(&mut self, game: &Game, board: &Board, time_limit: u128) -> Action {
+        let mut sim = self.search.start(board, game);
+        sim.set_exploration(self.exploration);
+        let root = sim.current_state();
+        let budget = (time_limit * 3 / 4).max(1) as u32;
+        let stats = sim.simulate3(root, self.depth, self.width, budget);
+
+        let action = sim
+            .best_action(root, self.selection)
+            .filter(|a| a.validate(game, board, true).is_ok())
+            .unwrap_or(Action::WAIT);
+        eprintln!("mcts {}. picked: {}", stats, action);
+        self.search.finish(sim, action);
+        action
+    }
+}
+
+/// Root parallel `Simulation` on every available core. The trees are built
+/// again every turn.
+pub struct ParallelMctsStrategy {
+    search: ParallelSearch,
+    selection: RootSelection,
+}
+
+impl Default for ParallelMctsStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParallelMctsStrategy {
+    pub fn new() -> ParallelMctsStrategy {
+        let threads = std::thread::available_parallelism().map_or(1, |x| x.get());
+        ParallelMctsStrategy::with_threads(threads)
+    }
+
+    pub fn with_threads(threads: usize) -> ParallelMctsStrategy {
+        ParallelMctsStrategy {
+            search: ParallelSearch::new(threads),
+            selection: RootSelection::MostPicks,
+        }
+    }
+
+    /// The constant `c` of UCB in every search.
+    pub fn with_exploration(mut self, c: f64) -> ParallelMctsStrategy {
+        self.search.exploration = c;
+        self
+    }
+}
+
+impl Strategy for ParallelMctsStrategy {
+    fn on_start(&mut self, _board: &Board) {}
+
+    fn get_next(&mut self, game: &Game, board: &Board, time_limit: u128) -> Action {
+        let budget = (time_limit * 3 / 4).max(1) as u32;
+        let result = self.search.run(board, game, budget);
+
+        let action = result
+            .best_action(self.selection)
+            .filter(|a| a.validate(game, board, true).is_ok())
+            .unwrap_or(Action::WAIT);
+        eprintln!(
+            "mcts-parallel x{} {}. picked: {}",
+            self.search.threads, result.stats, action
+        );
+        action
+    }
+}
+
+pub const STRATEGY_NAMES: [&str; 7] = [
+    "beam",
+    "beam-opponent",
+    "days",
+    "mcts",
+    "mcts-parallel",
+    "wood",
+    "random",
+];
+
+/// One of `STRATEGY_NAMES`. The UCB constant of `mcts` and `mcts-parallel`
+/// can follow a colon, as in `mcts:10`.
+pub fn by_name(name: &str) -> Option<Box<dyn Strategy>> {
+    match name {
+        "beam" => Some(Box::new(BeamSearch::new())),
+        "beam-opponent" => Some(Box::new(
+            BeamSearch::new()
+                .with_opponent(Box::new(BestResponses { k: 3 }), Aggregation::WorstCase),
+        )),
+        "days" => Some(Box::new(DayPlanStrategy::new())),
+        "mcts" => Some(Box::new(MctsStrategy::new())),
+        "mcts-parallel" => Some(Box::new(ParallelMctsStrategy::new())),
+        "wood" => Some(Box::new(WoodStrategy::new())),
+        "random" => Some(Box::new(RandomStrategy::new())),
+        _ => {
+            let (name, c) = name.split_once(':')?;
+            let c = c
+                .parse::<f64>()
+                .ok()
+                .filter(|c| *c >= 0.0 && c.is_finite())?;
+            match name {
+                "mcts" => Some(Box::new(MctsStrategy::new().with_exploration(c))),
+                "mcts-parallel" => Some(Box::new(ParallelMctsStrategy::new().with_exploration(c))),
+                _ => None,
+            }
+        }
+    }
+}
@@ -1,12 +1,17 @@
 use core::panic;
-use std::{collections::BTreeSet, fmt::Debug, iter::FromIterator, str::FromStr, usize};
-
-use itertools::Itertools;
+use std::{
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    iter::FromIterator,
+    str::FromStr,
+    usize,
+};
 
 use super::common::ParseError;
 use super::parse::*;
+use super::zobrist;
 
-#[derive(Hash, PartialEq, Eq, Clone)]
+#[derive(Hash, PartialEq, Eq, Clone, Copy)]
 pub struct Tree {
     index: u8,
     size: u8,
@@ -24,6 +29,16 @@ impl Debug for Tree {
     }
 }
 
+impl Display for Tree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.index, self.size, self.is_mine as u8, self.is_dormant as u8
+        )
+    }
+}
+
 impl Tree {
     pub fn index(&self) -> u8 {
         self.index
@@ -47,158 +62,177 @@ impl Tree {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/// All trees of the 37-cell board as bit masks: bit `i` of a mask is cell `i`.
+/// Every tree is in exactly one owner mask and one size mask. The Zobrist key
+/// of the collection is updated along with the masks.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
 pub struct TreeCollection {
-    trees: Vec<Option<Tree>>,
-    trees_by_size: Vec<u8>,
-    player_trees: BTreeSet<u8>,
-    enemy_trees: BTreeSet<u8>,
+    owner: [u64; 2],
+    sizes: [u64; 4],
+    dormant: u64,
+    key: u64,
+}
+
+impl Hash for TreeCollection {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.key);
+    }
 }
 
-/*
 impl Debug for TreeCollection {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "trees: {:?}", self.trees)
+        f.debug_list().entries(self.iter()).finish()
     }
-}*/
+}
+
+/// Iterates over the set bits of a mask, from the lowest.
+struct Bits(u64);
+
+impl Iterator for Bits {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.0 == 0 {
+            return None;
+        }
+        let index = self.0.trailing_zeros() as u8;
+        self.0 &= self.0 - 1;
+        Some(index)
+    }
+}
 
 impl TreeCollection {
-    fn size_index(index: u8, is_player: bool) -> usize {
-        let offset: usize = if is_player { 0 } else { 4 };
-        return offset + index as usize;
+    fn bit(index: u8) -> u64 {
+        1u64.checked_shl(index as u32).unwrap_or(0)
+    }
+
+    fn owner_index(is_player: bool) -> usize {
+        if is_player {
+            0
+        } else {
+            1
+        }
     }
 
     pub fn len(&self, is_player: bool) -> u8 {
-        (0..4).map(|i| self.get_amount_of_size(i, is_player)).sum()
+        self.mask(is_player).count_ones() as u8
     }
 
     pub fn empty() -> Self {
-        Self::new(Vec::new())
+        Self::default()
     }
 
-    fn tree_added(&mut self, index: u8, is_player: bool) {
-        if is_player {
-            self.player_trees.insert(index);
-        } else {
-            self.enemy_trees.insert(index);
-        }
+    /// Cells with a tree of the given player.
+    pub fn mask(&self, is_player: bool) -> u64 {
+        self.owner[Self::owner_index(is_player)]
     }
 
-    fn tree_removed(&mut self, index: u8, is_player: bool) {
-        if is_player {
-            self.player_trees.remove(&index);
-        } else {
-            self.enemy_trees.remove(&index);
+    /// Cells with a tree of the given size of both players.
+    pub fn size_mask(&self, size: u8) -> u64 {
+        self.sizes[size as usize]
+    }
+
+    pub fn dormant_mask(&self) -> u64 {
+        self.dormant
+    }
+
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    fn insert(&mut self, tree: Tree) {
+        self.remove(tree.index);
+        let bit = Self::bit(tree.index);
+        self.owner[Self::owner_index(tree.is_mine)] |= bit;
+        self.sizes[tree.size as usize] |= bit;
+        self.key ^= zobrist::tree(tree.index, tree.size, tree.is_mine);
+        if tree.is_dormant {
+            self.set_dormant(tree.index, true);
         }
     }
 
     pub fn seed(&mut self, index: u8, is_player: bool) {
-        self.trees[index as usize] = Some(Tree::new(index, 0, is_player, true));
-        self.trees_by_size[Self::size_index(0, is_player)] += 1;
-        self.tree_added(index, is_player);
+        self.insert(Tree::new(index, 0, is_player, true));
     }
 
     pub fn remove(&mut self, index: u8) {
-        if let Some(t) = std::mem::replace(&mut self.trees[index as usize], None) {
-            self.trees_by_size[Self::size_index(t.size, t.is_mine)] -= 1;
-            self.tree_removed(index, t.is_mine());
+        if !self.has_at(index) {
+            return;
         }
-    }
-
-    pub fn get(&self, index: u8) -> &Tree {
-        if let Some(Some(ref x)) = self.trees.get(index as usize) {
-            return x;
+        let tree = self.get(index);
+        self.key ^= zobrist::tree(index, tree.size, tree.is_mine);
+        if tree.is_dormant {
+            self.key ^= zobrist::dormant(index);
+        }
+        let keep = !Self::bit(index);
+        self.owner.iter_mut().for_each(|m| *m &= keep);
+        self.sizes.iter_mut().for_each(|m| *m &= keep);
+        self.dormant &= keep;
+    }
+
+    pub fn get(&self, index: u8) -> Tree {
+        let bit = Self::bit(index);
+        match (0..4u8).find(|s| self.sizes[*s as usize] & bit != 0) {
+            Some(size) => Tree::new(
+                index,
+                size,
+                self.owner[0] & bit != 0,
+                self.dormant & bit != 0,
+            ),
+            None => panic!("Invalid index"),
         }
-        panic!("Invalid index");
     }
 
-    pub fn get_mut(&mut self, index: u8) -> &mut Tree {
-        if let Some(Some(ref mut x)) = self.trees.get_mut(index as usize) {
-            return x;
+    pub fn set_dormant(&mut self, index: u8, is_dormant: bool) {
+        let bit = Self::bit(index);
+        if (self.dormant & bit != 0) != is_dormant {
+            self.dormant ^= bit;
+            self.key ^= zobrist::dormant(index);
         }
-        panic!("Invalid index");
     }
 
     pub fn grow_size(&mut self, index: u8) {
-        if let Some(ref mut x) = self.trees.get_mut(index as usize).unwrap() {
-            x.size += 1;
-            x.is_dormant = true;
-            self.trees_by_size[Self::size_index(x.size, x.is_mine)] += 1;
-            self.trees_by_size[Self::size_index(x.size - 1, x.is_mine)] -= 1;
+        let bit = Self::bit(index);
+        if let Some(size) = (0..3).find(|s| self.sizes[*s] & bit != 0) {
+            let is_mine = self.owner[0] & bit != 0;
+            self.sizes[size] &= !bit;
+            self.sizes[size + 1] |= bit;
+            self.key ^= zobrist::tree(index, size as u8, is_mine)
+                ^ zobrist::tree(index, size as u8 + 1, is_mine);
+            self.set_dormant(index, true);
         }
     }
 
     pub fn wake_up(&mut self) {
-        for item in self.trees.iter_mut() {
-            if let Some(ref mut t) = item {
-                t.set_dormant(false);
-            }
+        while self.dormant != 0 {
+            let index = self.dormant.trailing_zeros() as u8;
+            self.set_dormant(index, false);
         }
     }
 
     pub fn has_at(&self, index: u8) -> bool {
-        if let Some(Some(_)) = self.trees.get(index as usize) {
-            return true;
-        }
-        return false;
+        (self.owner[0] | self.owner[1]) & Self::bit(index) != 0
     }
 
     pub fn new(map: Vec<Tree>) -> Self {
-        let mut trees_by_size: Vec<u8> = vec![0, 0, 0, 0, 0, 0, 0, 0];
-        let mut _trees: Vec<Option<Tree>> = (0..37).map(|_| None).collect_vec();
-        let mut player_trees = BTreeSet::new();
-        let mut enemy_trees = BTreeSet::new();
-
-        for t in map {
-            let i = t.index();
-
-            match (t.is_mine, t.size) {
-                (true, x) if x <= 3 => {
-                    trees_by_size[t.size as usize] += 1;
-                    player_trees.insert(i);
-                }
-                (false, y) if y <= 3 => {
-                    trees_by_size[t.size as usize + 4] += 1;
-                    enemy_trees.insert(i);
-                }
-                _ => panic!("Incorrect size: {} for is_mine: {}", t.size, t.is_mine),
-            }
-            _trees[i as usize] = Some(t);
-        }
-
-        Self {
-            trees: _trees,
-            trees_by_size,
-            player_trees,
-            enemy_trees,
-        }
+        map.into_iter().collect()
     }
 
     pub fn get_amount_of_size(&self, size: u8, is_mine: bool) -> u8 {
-        let offset = if is_mine { 0 } else { 4 };
-        self.trees_by_size[offset + size as usize]
+        (self.sizes[size as usize] & self.mask(is_mine)).count_ones() as u8
     }
 
     #[cfg(test)]
-    pub fn my_trees(&self) -> impl Iterator<Item = &Tree> {
-        self.trees.iter().flatten().filter(|t| t.is_mine)
-        /*
-        self.player_trees
-            .iter()
-            .map(move |i| &self.trees[*i as usize])
-            .flatten()
-            */
+    pub fn my_trees(&self) -> impl Iterator<Item = Tree> + '_ {
+        self.iter_trees_for(true)
     }
 
-    pub fn iter_trees_for(&self, is_player: bool) -> impl Iterator<Item = &Tree> {
-        self.trees
-            .iter()
-            .flatten()
-            .filter(move |t| t.is_mine == is_player)
+    pub fn iter_trees_for(&self, is_player: bool) -> impl Iterator<Item = Tree> + '_ {
+        Bits(self.mask(is_player)).map(move |i| self.get(i))
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &Tree> {
-        self.trees.iter().flatten()
+    pub fn iter(&self) -> impl Iterator<Item = Tree> + '_ {
+        Bits(self.owner[0] | self.owner[1]).map(move |i| self.get(i))
     }
 
     #[cfg(test)]
@@ -215,7 +249,14 @@ impl TreeCollection {
 
 impl FromIterator<Tree> for TreeCollection {
     fn from_iter<T: IntoIterator<Item = Tree>>(iter: T) -> Self {
-        TreeCollection::new(iter.into_iter().collect())
+        let mut trees = Self::default();
+        for t in iter {
+            if t.size > 3 {
+                panic!("Incorrect size: {} for is_mine: {}", t.size, t.is_mine);
+            }
+            trees.insert(t);
+        }
+        trees
     }
 }
 
@@ -254,6 +295,7 @@ impl FromStr for Tree {
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
 
     use super::*;
 
@@ -263,11 +305,17 @@ mod tests {
         assert_eq!(t, Ok(Tree::new(29, 1, true, false)))
     }
 
+    #[test]
+    fn it_prints() {
+        assert_eq!(Tree::new(29, 1, true, false).to_string(), "29 1 1 0");
+        assert_eq!(Tree::new(3, 0, false, true).to_string(), "3 0 0 1");
+    }
+
     #[test]
     fn test_seed_tree() {
         let mut trees: TreeCollection = vec![Tree::new(0, 1, true, false)].into_iter().collect();
         trees.seed(1, true);
-        assert_eq!(trees.get(1), &Tree::new(1, 0, true, true));
+        assert_eq!(trees.get(1), Tree::new(1, 0, true, true));
         assert_eq!(trees.get_amount_of_size(1, true), 1);
         assert_eq!(trees.get_amount_of_size(0, true), 1);
     }
@@ -316,4 +364,34 @@ mod tests {
             vec![0, 1, 2, 3, 6, 10, 11, 21, 26]
         );
     }
+
+    #[test]
+    fn test_key_does_not_depend_on_history() {
+        let mut trees = TreeCollection::from_strings(vec!["7 1 1 0", "8 2 0 1", "20 3 1 0"]);
+        trees.seed(9, true);
+        trees.grow_size(7);
+        trees.remove(20);
+        trees.wake_up();
+        trees.set_dormant(8, true);
+
+        let expected = TreeCollection::from_strings(vec!["7 2 1 0", "8 2 0 1", "9 0 1 0"]);
+        assert_eq!(trees, expected);
+        assert_eq!(trees.key(), expected.key());
+        assert_ne!(trees.key(), TreeCollection::empty().key());
+    }
+
+    #[test]
+    fn test_dormancy() {
+        let mut trees = TreeCollection::from_strings(vec!["7 1 1 0", "8 2 0 0"]);
+        trees.set_dormant(8, true);
+        assert!(trees.get(8).is_dormant());
+
+        trees.grow_size(7);
+        assert_eq!(trees.get(7), Tree::new(7, 2, true, true));
+        assert_eq!(trees.dormant_mask(), 1 << 7 | 1 << 8);
+
+        trees.wake_up();
+        assert!(trees.iter().all(|t| t.not_dormant()));
+        assert_eq!(trees.size_mask(2), 1 << 7 | 1 << 8);
+    }
 }
@@ -0,0 +1,68 @@
+//! Random keys for Zobrist hashing. The tables are generated at compile time
+//! from a fixed seed, so keys are the same in every run and every binary.
+
+const fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+const fn tree_table() -> [[u64; 8]; 37] {
+    let mut table = [[0u64; 8]; 37];
+    let mut cell = 0;
+    while cell < 37 {
+        let mut kind = 0;
+        while kind < 8 {
+            table[cell][kind] = splitmix64((cell * 8 + kind) as u64);
+            kind += 1;
+        }
+        cell += 1;
+    }
+    table
+}
+
+const fn dormant_table() -> [u64; 37] {
+    let mut table = [0u64; 37];
+    let mut cell = 0;
+    while cell < 37 {
+        table[cell] = splitmix64(0x1_0000 + cell as u64);
+        cell += 1;
+    }
+    table
+}
+
+const TREES: [[u64; 8]; 37] = tree_table();
+const DORMANT: [u64; 37] = dormant_table();
+
+/// Key of a tree of the given owner and size standing on `index`.
+pub fn tree(index: u8, size: u8, is_mine: bool) -> u64 {
+    let owner = if is_mine { 0 } else { 4 };
+    TREES[index as usize][owner + size as usize]
+}
+
+pub fn dormant(index: u8) -> u64 {
+    DORMANT[index as usize]
+}
+
+/// Key of a numeric part of the state, like sun points or the day. `field`
+/// tells the parts apart, so equal values of different fields don't cancel.
+pub fn value(field: u8, value: u16) -> u64 {
+    splitmix64(0x2_0000_0000 + ((field as u64) << 16) + value as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_keys_are_distinct() {
+        let mut keys: HashSet<u64> = TREES.iter().flatten().cloned().collect();
+        keys.extend(DORMANT.iter().cloned());
+        keys.extend((0..8).flat_map(|f| (0..200).map(move |v| value(f, v))));
+
+        assert_eq!(keys.len(), 37 * 8 + 37 + 8 * 200);
+    }
+}
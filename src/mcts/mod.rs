@@ -1,3 +1,7 @@
-use super::engine::{actions::*, board::*, common::*, game::*, tree::*};
+//! Monte Carlo tree search that only knows the game through `GameState`.
 pub mod nodes;
+pub mod playout;
+pub mod policy;
 pub mod simulation;
+pub mod state;
+pub mod strategy;
@@ -1,72 +1,141 @@
 use std::usize;
+
+use super::policy::Arm;
 pub type TreeIndex = usize;
 pub type Score = f32;
 
-use crate::engine::actions::Action;
-
-pub struct PlayerNode {
-    action: Action,
-    score: Score,
-    picks: u32,
-    parent_state: TreeIndex,
-    child_actions: Vec<TreeIndex>,
+/// A move of the player in the state `parent_state`. In the sequential scheme
+/// its children are the replies of the enemy, in the decoupled one it has no
+/// children and only keeps the statistics of the move.
+pub struct PlayerNode<M> {
+    pub action: M,
+    pub score: Score,
+    pub picks: u32,
+    /// Kept by the selection policy.
+    pub value: Score,
+    pub parent_state: TreeIndex,
+    pub child_actions: Vec<TreeIndex>,
 }
 
-pub struct EnemyNode {
-    action: Action,
-    score: Score,
-    picks: u32,
-    parent_action: TreeIndex,
-    child_state: TreeIndex,
+/// A move of the enemy, with the score from the enemy's point of view. In the
+/// sequential scheme it is a reply to the player node `parent` and the state
+/// after both moves is created when the node is picked for the first time.
+/// In the decoupled scheme `parent` is the state and there is no child.
+pub struct EnemyNode<M> {
+    pub action: M,
+    pub score: Score,
+    pub picks: u32,
+    pub value: Score,
+    pub parent: TreeIndex,
+    pub child_state: Option<TreeIndex>,
 }
 
+/// A state of the game, stored in `games` of the simulation at index `game`.
+/// `child_nodes` are the player moves. The decoupled scheme also keeps the
+/// enemy moves here and the state after each pair of moves, at index
+/// `player * enemy_nodes.len() + enemy` of `joint_states`.
 pub struct StateNode {
-    score: Score,
-    wins: u32,
-    parent: TreeIndex,
-    game: TreeIndex,
-    is_explored: bool,
-    child_nodes: Vec<TreeIndex>,
-    merged_parents: Vec<TreeIndex>,
+    pub score: Score,
+    pub picks: u32,
+    /// The state one turn before.
+    pub parent: Option<TreeIndex>,
+    pub game: TreeIndex,
+    pub child_nodes: Vec<TreeIndex>,
+    pub enemy_nodes: Vec<TreeIndex>,
+    pub joint_states: Vec<Option<TreeIndex>>,
 }
 
 pub trait GameNode {
-    type TChild;
-    type TParent;
+    fn score(&self) -> Score;
 
-    fn parent(&self) -> TreeIndex;
+    fn picks(&self) -> u32;
+
+    fn value(&self) -> Score {
+        0.0
+    }
 
-    fn child_nodes(&self) -> Vec<TreeIndex>;
+    fn parent(&self) -> Option<TreeIndex>;
+
+    fn child_nodes(&self) -> &[TreeIndex];
 
     fn is_expanded(&self) -> bool {
         self.child_nodes().len() > 0
     }
+
+    fn avg_score(&self) -> Score {
+        self.score() / self.picks() as Score
+    }
+
+    fn arm(&self) -> Arm {
+        Arm {
+            score: self.score(),
+            picks: self.picks(),
+            value: self.value(),
+        }
+    }
 }
 
 impl GameNode for StateNode {
-    type TChild = PlayerNode;
+    fn score(&self) -> Score {
+        self.score
+    }
+
+    fn picks(&self) -> u32 {
+        self.picks
+    }
+
+    fn parent(&self) -> Option<TreeIndex> {
+        self.parent
+    }
+
+    fn child_nodes(&self) -> &[TreeIndex] {
+        &self.child_nodes
+    }
+}
 
-    type TParent = EnemyNode;
+impl<M> GameNode for PlayerNode<M> {
+    fn score(&self) -> Score {
+        self.score
+    }
+
+    fn picks(&self) -> u32 {
+        self.picks
+    }
 
-    fn parent(&self) -> TreeIndex {
-        todo!()
+    fn value(&self) -> Score {
+        self.value
     }
 
-    fn child_nodes(&self) -> Vec<TreeIndex> {
-        todo!()
+    fn parent(&self) -> Option<TreeIndex> {
+        Some(self.parent_state)
+    }
+
+    fn child_nodes(&self) -> &[TreeIndex] {
+        &self.child_actions
     }
 }
 
-impl GameNode for PlayerNode {
-    type TChild = EnemyNode;
+impl<M> GameNode for EnemyNode<M> {
+    fn score(&self) -> Score {
+        self.score
+    }
+
+    fn picks(&self) -> u32 {
+        self.picks
+    }
 
-    type TParent = StateNode;
+    fn value(&self) -> Score {
+        self.value
+    }
 
-    fn parent(&self) -> TreeIndex {
-        todo!()
+    fn parent(&self) -> Option<TreeIndex> {
+        Some(self.parent)
     }
 
-    fn child_nodes(&self) -> Vec<TreeIndex> {
-        todo!()
+    fn child_nodes(&self) -> &[TreeIndex] {
+        match self.child_state {
+            Some(ref state) => std::slice::from_ref(state),
+            None => &[],
+        }
     }
 }
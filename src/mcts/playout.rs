@@ -0,0 +1,163 @@
+use rand::{prelude::SliceRandom, rngs::StdRng};
+
+use crate::engine::{actions::Action, simulation::Simulation};
+
+use super::{
+    nodes::Score,
+    state::{GameState, SpringState},
+};
+
+/// Plays a game on from a leaf of the tree to get its reward.
+pub trait PlayoutPolicy<S: GameState> {
+    /// The move of one side in a state that is not terminal.
+    fn pick(&self, state: &S, is_player: bool, rng: &mut StdRng) -> S::Move;
+
+    /// Turns to play before `GameState::evaluate` is used instead of the
+    /// reward. `None` plays to the end of the game.
+    fn cutoff(&self) -> Option<u32> {
+        None
+    }
+
+    fn playout(&self, state: &S, rng: &mut StdRng) -> Score {
+        let mut state = state.clone();
+        let mut turns = 0;
+        while !state.is_terminal() {
+            if self.cutoff().is_some_and(|c| turns >= c) {
+                return state.evaluate();
+            }
+            let player = self.pick(&state, true, rng);
+            let enemy = self.pick(&state, false, rng);
+            state = state.apply(player, enemy);
+            turns += 1;
+        }
+        state.reward()
+    }
+}
+
+/// Uniformly random moves.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomPlayout {
+    pub cutoff: Option<u32>,
+}
+
+impl<S: GameState> PlayoutPolicy<S> for RandomPlayout {
+    fn pick(&self, state: &S, is_player: bool, rng: &mut StdRng) -> S::Move {
+        *state.moves(is_player).choose(rng).unwrap()
+    }
+
+    fn cutoff(&self) -> Option<u32> {
+        self.cutoff
+    }
+}
+
+/// Random moves with the odds of `Simulation::weight_of`. Uniform when no
+/// move is worth trying.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WeightedPlayout {
+    pub cutoff: Option<u32>,
+}
+
+impl<'a> PlayoutPolicy<SpringState<'a>> for WeightedPlayout {
+    fn pick(&self, state: &SpringState<'a>, is_player: bool, rng: &mut StdRng) -> Action {
+        let moves = state.moves(is_player);
+        let weight = |a: &Action| Simulation::weight_of(&state.game, state.board, a);
+        match moves.choose_weighted(rng, weight) {
+            Ok(action) => *action,
+            Err(_) => *moves.choose(rng).unwrap(),
+        }
+    }
+
+    fn cutoff(&self) -> Option<u32> {
+        self.cutoff
+    }
+}
+
+/// The move of the highest `Simulation::weight_of`, ties broken at random.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GreedyPlayout {
+    pub cutoff: Option<u32>,
+}
+
+impl<'a> PlayoutPolicy<SpringState<'a>> for GreedyPlayout {
+    fn pick(&self, state: &SpringState<'a>, is_player: bool, rng: &mut StdRng) -> Action {
+        let moves = state.moves(is_player);
+        let weights: Vec<u32> = moves
+            .iter()
+            .map(|a| Simulation::weight_of(&state.game, state.board, a))
+            .collect();
+        let best = *weights.iter().max().unwrap();
+        let candidates: Vec<&Action> = moves
+            .iter()
+            .zip(weights)
+            .filter(|(_, w)| *w == best)
+            .map(|(a, _)| a)
+            .collect();
+        **candidates.choose(rng).unwrap()
+    }
+
+    fn cutoff(&self) -> Option<u32> {
+        self.cutoff
+    }
+}
+
+pub const PLAYOUT_NAMES: [&str; 3] = ["random", "weighted", "greedy"];
+
+pub fn by_name<'a>(name: &str) -> Option<Box<dyn PlayoutPolicy<SpringState<'a>> + Send>> {
+    match name {
+        "random" => Some(Box::new(RandomPlayout::default())),
+        "weighted" => Some(Box::new(WeightedPlayout::default())),
+        "greedy" => Some(Box::new(GreedyPlayout::default())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::engine::{board::Board, game::Game};
+
+    use super::*;
+
+    fn start<'a>(board: &'a Board) -> SpringState<'a> {
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "2 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        SpringState::new(board, game)
+    }
+
+    #[test]
+    fn test_playouts_reach_the_end() {
+        let board = Board::default();
+        let mut rng = StdRng::seed_from_u64(1);
+        for name in &PLAYOUT_NAMES {
+            let reward = by_name(name).unwrap().playout(&start(&board), &mut rng);
+            assert!([0.0, 0.5, 1.0].contains(&reward), "{} {}", name, reward);
+        }
+    }
+
+    #[test]
+    fn test_cutoff_evaluates() {
+        let board = Board::default();
+        let mut rng = StdRng::seed_from_u64(1);
+        let state = start(&board);
+
+        let policy = GreedyPlayout { cutoff: Some(0) };
+        assert_eq!(policy.playout(&state, &mut rng), state.evaluate());
+
+        let reward = WeightedPlayout { cutoff: Some(3) }.playout(&state, &mut rng);
+        assert!(reward > 0.0 && reward < 1.0);
+    }
+
+    #[test]
+    fn test_greedy_completes() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "22", "20", "8 0", "2 0 0", "4", "20 3 1 0", "24 1 0 0", "29 1 1 0", "33 1 0 0",
+        ]);
+        let mut rng = StdRng::seed_from_u64(1);
+        let action = GreedyPlayout::default().pick(&SpringState::new(&board, game), true, &mut rng);
+
+        assert_eq!(action, Action::COMPLETE(20));
+    }
+}
@@ -0,0 +1,231 @@
+use rand::{rngs::StdRng, Rng};
+
+use super::nodes::Score;
+
+/// What a selection policy knows about one move of a node. `value` belongs
+/// to the policy and is changed only through `SelectionPolicy::update`.
+#[derive(Debug, Clone, Copy)]
+pub struct Arm {
+    pub score: Score,
+    pub picks: u32,
+    pub value: Score,
+}
+
+/// Picks one of the moves of a node in the tree. Rewards are from 0 to 1
+/// from the point of view of the side that picks.
+pub trait SelectionPolicy {
+    /// Position of the picked arm and the probability it was picked with.
+    /// `picks` is how many times the node itself was picked.
+    fn select(&self, arms: &[Arm], picks: u32, rng: &mut StdRng) -> (usize, Score);
+
+    /// The change of `value` of `arm` after `picked`, picked with
+    /// `probability`, got `reward`. Called for every arm of the node.
+    fn update(&self, _arm: usize, _picked: usize, _probability: Score, _reward: Score) -> Score {
+        0.0
+    }
+}
+
+/// UCB1 with the exploration constant `c`. Unvisited arms go first.
+#[derive(Debug, Clone, Copy)]
+pub struct Ucb1 {
+    pub c: Score,
+}
+
+impl SelectionPolicy for Ucb1 {
+    fn select(&self, arms: &[Arm], picks: u32, _rng: &mut StdRng) -> (usize, Score) {
+        let ucb = |arm: &Arm| {
+            if arm.picks == 0 {
+                return Score::INFINITY;
+            }
+            let average = arm.score / arm.picks as Score;
+            average + self.c * ((picks as Score).ln() / arm.picks as Score).sqrt()
+        };
+        let mut best = 0;
+        let mut best_ucb = ucb(&arms[0]);
+        for (i, arm) in arms.iter().enumerate().skip(1) {
+            let value = ucb(arm);
+            if value > best_ucb {
+                best = i;
+                best_ucb = value;
+            }
+        }
+        (best, 1.0)
+    }
+}
+
+/// EXP3: picks at random with weights growing exponentially in the reward
+/// estimated by importance sampling, mixed with `gamma` of uniform play.
+#[derive(Debug, Clone, Copy)]
+pub struct Exp3 {
+    pub gamma: Score,
+}
+
+impl Exp3 {
+    fn probabilities(&self, arms: &[Arm]) -> Vec<Score> {
+        let k = arms.len() as Score;
+        let eta = self.gamma / k;
+        let max = arms.iter().map(|a| a.value).fold(Score::MIN, Score::max);
+        let weights: Vec<Score> = arms.iter().map(|a| (eta * (a.value - max)).exp()).collect();
+        let total: Score = weights.iter().sum();
+        weights
+            .iter()
+            .map(|w| (1.0 - self.gamma) * w / total + self.gamma / k)
+            .collect()
+    }
+}
+
+impl SelectionPolicy for Exp3 {
+    fn select(&self, arms: &[Arm], _picks: u32, rng: &mut StdRng) -> (usize, Score) {
+        sample(&self.probabilities(arms), rng)
+    }
+
+    fn update(&self, arm: usize, picked: usize, probability: Score, reward: Score) -> Score {
+        if arm == picked {
+            reward / probability
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Regret matching: plays each arm in proportion to its positive regret,
+/// estimated from the picked arm only, mixed with `gamma` of uniform play.
+#[derive(Debug, Clone, Copy)]
+pub struct RegretMatching {
+    pub gamma: Score,
+}
+
+impl RegretMatching {
+    fn probabilities(&self, arms: &[Arm]) -> Vec<Score> {
+        let k = arms.len() as Score;
+        let total: Score = arms.iter().map(|a| a.value.max(0.0)).sum();
+        arms.iter()
+            .map(|a| {
+                let matched = if total > 0.0 {
+                    a.value.max(0.0) / total
+                } else {
+                    1.0 / k
+                };
+                (1.0 - self.gamma) * matched + self.gamma / k
+            })
+            .collect()
+    }
+}
+
+impl SelectionPolicy for RegretMatching {
+    fn select(&self, arms: &[Arm], _picks: u32, rng: &mut StdRng) -> (usize, Score) {
+        sample(&self.probabilities(arms), rng)
+    }
+
+    fn update(&self, arm: usize, picked: usize, probability: Score, reward: Score) -> Score {
+        let estimate = if arm == picked {
+            reward / probability
+        } else {
+            0.0
+        };
+        estimate - reward
+    }
+}
+
+fn sample(probabilities: &[Score], rng: &mut StdRng) -> (usize, Score) {
+    let mut left: Score = rng.gen::<Score>() * probabilities.iter().sum::<Score>();
+    for (i, p) in probabilities.iter().enumerate() {
+        if left < *p {
+            return (i, *p);
+        }
+        left -= p;
+    }
+    let last = probabilities.len() - 1;
+    (last, probabilities[last])
+}
+
+pub const POLICY_NAMES: [&str; 3] = ["ucb", "exp3", "rm"];
+
+/// One of `POLICY_NAMES`, or `ucb:<c>` for UCB1 with another constant.
+pub fn by_name(name: &str) -> Option<Box<dyn SelectionPolicy + Send>> {
+    match name {
+        "ucb" => Some(Box::new(Ucb1 {
+            c: std::f32::consts::SQRT_2,
+        })),
+        "exp3" => Some(Box::new(Exp3 { gamma: 0.1 })),
+        "rm" => Some(Box::new(RegretMatching { gamma: 0.1 })),
+        _ => {
+            let c = name.strip_prefix("ucb:")?.parse::<Score>().ok()?;
+            if c >= 0.0 && c.is_finite() {
+                Some(Box::new(Ucb1 { c }))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn arms(values: &[Score]) -> Vec<Arm> {
+        values
+            .iter()
+            .map(|v| Arm {
+                score: 0.0,
+                picks: 0,
+                value: *v,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ucb_prefers_unvisited() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut stats = arms(&[0.0, 0.0]);
+        stats[0].picks = 10;
+        stats[0].score = 10.0;
+
+        assert_eq!(Ucb1 { c: 1.0 }.select(&stats, 10, &mut rng), (1, 1.0));
+    }
+
+    #[test]
+    fn test_ucb_constant_by_name() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut stats = arms(&[0.0, 0.0]);
+        stats[0].picks = 10;
+        stats[0].score = 6.0;
+        stats[1].picks = 1;
+
+        assert_eq!(
+            by_name("ucb:0").unwrap().select(&stats, 11, &mut rng),
+            (0, 1.0)
+        );
+        assert_eq!(
+            by_name("ucb:2").unwrap().select(&stats, 11, &mut rng),
+            (1, 1.0)
+        );
+        assert!(by_name("ucb:-1").is_none());
+        assert!(by_name("ucb:x").is_none());
+    }
+
+    #[test]
+    fn test_probabilities_sum_to_one() {
+        let stats = arms(&[3.0, -1.0, 0.5]);
+        for p in [
+            Exp3 { gamma: 0.1 }.probabilities(&stats),
+            RegretMatching { gamma: 0.1 }.probabilities(&stats),
+        ] {
+            assert!((p.iter().sum::<Score>() - 1.0).abs() < 1e-5);
+            assert!(p.iter().all(|x| *x >= 0.1 / 3.0));
+        }
+    }
+
+    #[test]
+    fn test_regret_matching_plays_positive_regret() {
+        let p = RegretMatching { gamma: 0.0 }.probabilities(&arms(&[3.0, -1.0, 1.0]));
+        assert_eq!(p, vec![0.75, 0.0, 0.25]);
+
+        let policy = RegretMatching { gamma: 0.0 };
+        assert_eq!(policy.update(0, 0, 0.5, 1.0), 1.0);
+        assert_eq!(policy.update(1, 0, 0.5, 1.0), -1.0);
+    }
+}
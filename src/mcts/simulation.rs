@@ -1,47 +1,630 @@
-use std::usize;
+use std::{
+    f32::consts::SQRT_2,
+    time::{Duration, Instant},
+};
 
-use crate::engine::board::*;
-use crate::engine::game::*;
+use rand::{rngs::StdRng, SeedableRng};
 
-use super::nodes::{EnemyNode, GameNode, PlayerNode, StateNode, TreeIndex};
+use super::{
+    nodes::{EnemyNode, GameNode, PlayerNode, Score, StateNode, TreeIndex},
+    playout::{PlayoutPolicy, RandomPlayout},
+    policy::{SelectionPolicy, Ucb1},
+    state::GameState,
+};
 
-pub struct MctsSimulation<'a> {
-    board: &'a Board,
-    games: Vec<Game>,
-    player_nodes: Vec<PlayerNode>,
-    enemy_nodes: Vec<EnemyNode>,
+/// How a turn of simultaneous moves is put into the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    /// The player moves first and the enemy picks a reply knowing the move,
+    /// which makes the search pessimistic.
+    Sequential,
+    /// Both sides pick their move in the state by their own statistics,
+    /// without knowing the other's move.
+    Decoupled,
+}
+
+/// Monte Carlo tree search over any `GameState`. States, player moves and
+/// enemy moves are kept in flat vectors and refer to each other by index.
+pub struct MctsSimulation<S: GameState> {
+    games: Vec<S>,
+    player_nodes: Vec<PlayerNode<S::Move>>,
+    enemy_nodes: Vec<EnemyNode<S::Move>>,
     states: Vec<StateNode>,
-    x: Option<StateNode>,
+    scheme: Scheme,
+    policy: Box<dyn SelectionPolicy + Send>,
+    playout: Box<dyn PlayoutPolicy<S> + Send>,
+    static_weight: Score,
+    rng: StdRng,
 }
 
-impl<'a> MctsSimulation<'a> {
-    pub fn new() -> Self {
-        todo!()
+/// One turn on the path of an iteration: the positions of the picked moves
+/// among their siblings and the probabilities they were picked with.
+struct Step {
+    state: TreeIndex,
+    player: usize,
+    enemy: usize,
+    player_probability: Score,
+    enemy_probability: Score,
+}
+
+impl<S: GameState> MctsSimulation<S> {
+    pub fn new(root: S) -> Self {
+        Self::with_seed(root, rand::random())
+    }
+
+    /// A simulation whose random playouts are the same for the same seed.
+    pub fn with_seed(root: S, seed: u64) -> Self {
+        let mut result = Self {
+            games: Vec::new(),
+            player_nodes: Vec::new(),
+            enemy_nodes: Vec::new(),
+            states: Vec::new(),
+            scheme: Scheme::Sequential,
+            policy: Box::new(Ucb1 { c: SQRT_2 }),
+            playout: Box::new(RandomPlayout::default()),
+            static_weight: 0.0,
+            rng: StdRng::seed_from_u64(seed),
+        };
+        result.create_state(root, None);
+        result
+    }
+
+    /// Must be set before the first iteration.
+    pub fn with_scheme(mut self, scheme: Scheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    /// Used by both sides. UCB1 with `c` of `SQRT_2` by default.
+    pub fn with_policy(mut self, policy: Box<dyn SelectionPolicy + Send>) -> Self {
+        self.policy = policy;
+        self
     }
 
-    pub fn get_or_create_state(&mut self, game: &Game) -> TreeIndex {
-        if let Some(ref mut x) = self.x {}
-        todo!()
+    /// Plays from new states. Uniformly random to the end by default.
+    pub fn with_playout(mut self, playout: Box<dyn PlayoutPolicy<S> + Send>) -> Self {
+        self.playout = playout;
+        self
     }
 
-    pub fn iterate(&mut self, state: TreeIndex) {
-        let current_state = state;
+    /// Share of `GameState::evaluate` of the new state in its reward, the
+    /// rest comes from the playout. With 1 there is no playout at all.
+    pub fn with_static_weight(mut self, weight: Score) -> Self {
+        self.static_weight = weight;
+        self
+    }
+
+    pub fn root(&self) -> TreeIndex {
+        0
+    }
+
+    pub fn iterations(&self) -> u32 {
+        self.states[self.root()].picks
+    }
+
+    pub fn states_len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn game(&self, state: TreeIndex) -> &S {
+        &self.games[self.states[state].game]
+    }
+
+    pub fn run(&mut self, iterations: u32) {
+        for _ in 0..iterations {
+            self.iterate();
+        }
+    }
+
+    /// Iterates until `budget` has passed, at least once. The clock is read
+    /// every few iterations only. Returns the number of iterations.
+    pub fn run_for(&mut self, budget: Duration) -> u32 {
+        const CHECK_EVERY: u32 = 8;
+        let deadline = Instant::now() + budget;
+        let mut iterations = 0;
         loop {
-            self.ensure_expanded::<StateNode>(current_state);
+            self.iterate();
+            iterations += 1;
+            if iterations % CHECK_EVERY == 0 && Instant::now() >= deadline {
+                return iterations;
+            }
+        }
+    }
+
+    /// One iteration: walks down the tree by the selection policy until a
+    /// new state is added, evaluates it and backs the reward up the path.
+    pub fn iterate(&mut self) {
+        let mut state = self.root();
+        let mut path = Vec::new();
+        while !self.game(state).is_terminal() {
+            if !self.states[state].is_expanded() {
+                self.expand(state);
+            }
+            let step = self.select(state);
+            let next = self.next_state(&step);
+            let (player, enemy) = (self.player_id(&step), self.enemy_id(&step));
+            path.push(step);
 
-            for player_node_id in self.states[current_state].child_nodes() {
-                for enemy_node_id in self.player_nodes[player_node_id].child_nodes() {
-                    self.states.push(Self::create_state_node());
+            match next {
+                Some(next) => state = next,
+                None => {
+                    let player_move = self.player_nodes[player].action;
+                    let enemy_move = self.enemy_nodes[enemy].action;
+                    let next_game = self.game(state).apply(player_move, enemy_move);
+                    let next = self.create_state(next_game, Some(state));
+                    self.set_next_state(path.last().unwrap(), next);
+                    state = next;
+                    break;
                 }
             }
         }
+
+        let reward = self.playout(state);
+        self.backup(state, &path, reward);
     }
 
-    fn create_state_node() -> StateNode {
-        todo!()
+    /// Picks the moves of both sides in `state` with the policy.
+    fn select(&mut self, state: TreeIndex) -> Step {
+        fn pick<N: GameNode>(
+            policy: &dyn SelectionPolicy,
+            nodes: &[N],
+            children: &[TreeIndex],
+            picks: u32,
+            rng: &mut StdRng,
+        ) -> (usize, Score) {
+            let arms: Vec<_> = children.iter().map(|c| nodes[*c].arm()).collect();
+            policy.select(&arms, picks, rng)
+        }
+
+        let node = &self.states[state];
+        let policy = &self.policy;
+        let rng = &mut self.rng;
+        let (player, player_probability) = pick(
+            policy.as_ref(),
+            &self.player_nodes,
+            &node.child_nodes,
+            node.picks,
+            rng,
+        );
+        let (enemies, picks) = match self.scheme {
+            Scheme::Sequential => {
+                let player_node = &self.player_nodes[node.child_nodes[player]];
+                (&player_node.child_actions, player_node.picks)
+            }
+            Scheme::Decoupled => (&node.enemy_nodes, node.picks),
+        };
+        let (enemy, enemy_probability) =
+            pick(policy.as_ref(), &self.enemy_nodes, enemies, picks, rng);
+        Step {
+            state,
+            player,
+            enemy,
+            player_probability,
+            enemy_probability,
+        }
     }
 
-    pub fn ensure_expanded<T: GameNode>(&mut self, node_id: TreeIndex) {
-        todo!()
+    fn player_id(&self, step: &Step) -> TreeIndex {
+        self.states[step.state].child_nodes[step.player]
+    }
+
+    /// The enemy moves to pick from after the player move `player` in `state`.
+    fn enemy_children(&self, state: TreeIndex, player: usize) -> &[TreeIndex] {
+        let node = &self.states[state];
+        match self.scheme {
+            Scheme::Sequential => &self.player_nodes[node.child_nodes[player]].child_actions,
+            Scheme::Decoupled => &node.enemy_nodes,
+        }
+    }
+
+    fn enemy_id(&self, step: &Step) -> TreeIndex {
+        self.enemy_children(step.state, step.player)[step.enemy]
+    }
+
+    fn next_state(&self, step: &Step) -> Option<TreeIndex> {
+        match self.scheme {
+            Scheme::Sequential => self.enemy_nodes[self.enemy_id(step)].child_state,
+            Scheme::Decoupled => {
+                let node = &self.states[step.state];
+                node.joint_states[step.player * node.enemy_nodes.len() + step.enemy]
+            }
+        }
+    }
+
+    fn set_next_state(&mut self, step: &Step, next: TreeIndex) {
+        match self.scheme {
+            Scheme::Sequential => {
+                let enemy = self.enemy_id(step);
+                self.enemy_nodes[enemy].child_state = Some(next);
+            }
+            Scheme::Decoupled => {
+                let node = &mut self.states[step.state];
+                let enemies = node.enemy_nodes.len();
+                node.joint_states[step.player * enemies + step.enemy] = Some(next);
+            }
+        }
+    }
+
+    /// The most picked move of the player in the root.
+    pub fn best_move(&self) -> Option<S::Move> {
+        self.root_moves()
+            .into_iter()
+            .max_by_key(|(_, picks, _)| *picks)
+            .map(|(action, _, _)| action)
+    }
+
+    /// Every move of the player in the root with its picks and average score.
+    pub fn root_moves(&self) -> Vec<(S::Move, u32, Score)> {
+        self.states[self.root()]
+            .child_nodes
+            .iter()
+            .map(|p| &self.player_nodes[*p])
+            .map(|p| (p.action, p.picks, p.avg_score()))
+            .collect()
+    }
+
+    fn create_state(&mut self, game: S, parent: Option<TreeIndex>) -> TreeIndex {
+        self.games.push(game);
+        self.states.push(StateNode {
+            score: 0.0,
+            picks: 0,
+            parent,
+            game: self.games.len() - 1,
+            child_nodes: Vec::new(),
+            enemy_nodes: Vec::new(),
+            joint_states: Vec::new(),
+        });
+        self.states.len() - 1
+    }
+
+    /// Adds a player node for each move of the player. In the sequential
+    /// scheme each of them gets an enemy node for each move of the enemy, in
+    /// the decoupled one the enemy nodes belong to the state.
+    fn expand(&mut self, state: TreeIndex) {
+        let player_moves = self.game(state).moves(true);
+        let enemy_moves = self.game(state).moves(false);
+        for action in player_moves {
+            let player = self.player_nodes.len();
+            let child_actions = match self.scheme {
+                Scheme::Sequential => self.add_enemy_nodes(&enemy_moves, player),
+                Scheme::Decoupled => Vec::new(),
+            };
+            self.player_nodes.push(PlayerNode {
+                action,
+                score: 0.0,
+                picks: 0,
+                value: 0.0,
+                parent_state: state,
+                child_actions,
+            });
+            self.states[state].child_nodes.push(player);
+        }
+        if self.scheme == Scheme::Decoupled {
+            let enemy_nodes = self.add_enemy_nodes(&enemy_moves, state);
+            let node = &mut self.states[state];
+            node.joint_states = vec![None; node.child_nodes.len() * enemy_nodes.len()];
+            node.enemy_nodes = enemy_nodes;
+        }
+    }
+
+    fn add_enemy_nodes(&mut self, moves: &[S::Move], parent: TreeIndex) -> Vec<TreeIndex> {
+        let first = self.enemy_nodes.len();
+        self.enemy_nodes
+            .extend(moves.iter().map(|action| EnemyNode {
+                action: *action,
+                score: 0.0,
+                picks: 0,
+                value: 0.0,
+                parent,
+                child_state: None,
+            }));
+        (first..self.enemy_nodes.len()).collect()
+    }
+
+    /// The playout reward of `state` mixed with its static evaluation.
+    fn playout(&mut self, state: TreeIndex) -> Score {
+        let game = &self.games[self.states[state].game];
+        let w = self.static_weight;
+        if w >= 1.0 {
+            return game.evaluate();
+        }
+        let rollout = self.playout.playout(game, &mut self.rng);
+        if w <= 0.0 {
+            return rollout;
+        }
+        (1.0 - w) * rollout + w * game.evaluate()
+    }
+
+    /// Adds the reward to `state` and to every node of `path`. Enemy nodes
+    /// get `1 - reward`, so that they prefer what is worse for the player.
+    /// The policy updates its values of all the moves next to a picked one.
+    fn backup(&mut self, state: TreeIndex, path: &[Step], reward: Score) {
+        self.states[state].picks += 1;
+        self.states[state].score += reward;
+        for step in path {
+            for i in 0..self.states[step.state].child_nodes.len() {
+                let id = self.states[step.state].child_nodes[i];
+                let delta = self
+                    .policy
+                    .update(i, step.player, step.player_probability, reward);
+                self.player_nodes[id].value += delta;
+            }
+            for j in 0..self.enemy_children(step.state, step.player).len() {
+                let id = self.enemy_children(step.state, step.player)[j];
+                let delta = self
+                    .policy
+                    .update(j, step.enemy, step.enemy_probability, 1.0 - reward);
+                self.enemy_nodes[id].value += delta;
+            }
+
+            let enemy = self.enemy_id(step);
+            let enemy = &mut self.enemy_nodes[enemy];
+            enemy.picks += 1;
+            enemy.score += 1.0 - reward;
+
+            let player = self.player_id(step);
+            let player = &mut self.player_nodes[player];
+            player.picks += 1;
+            player.score += reward;
+
+            let parent = &mut self.states[step.state];
+            parent.picks += 1;
+            parent.score += reward;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{actions::Action, board::Board, game::Game};
+
+    use super::super::{playout::GreedyPlayout, policy, state::SpringState};
+    use super::*;
+
+    /// Both sides add 1, 2 or 3 to their total for three turns, the higher
+    /// total wins. Adding 3 is always best.
+    #[derive(Clone, Debug)]
+    struct Race {
+        turn: u8,
+        totals: [u32; 2],
+    }
+
+    impl GameState for Race {
+        type Move = u32;
+
+        fn moves(&self, _is_player: bool) -> Vec<u32> {
+            vec![1, 2, 3]
+        }
+
+        fn apply(&self, player: u32, enemy: u32) -> Self {
+            Race {
+                turn: self.turn + 1,
+                totals: [self.totals[0] + player, self.totals[1] + enemy],
+            }
+        }
+
+        fn is_terminal(&self) -> bool {
+            self.turn == 3
+        }
+
+        fn reward(&self) -> Score {
+            match self.totals[0].cmp(&self.totals[1]) {
+                std::cmp::Ordering::Less => 0.0,
+                std::cmp::Ordering::Equal => 0.5,
+                std::cmp::Ordering::Greater => 1.0,
+            }
+        }
+    }
+
+    /// One turn. Move 0 is a sure draw. Move 1 wins against reply 0 and
+    /// loses against reply 1, which the enemy will find.
+    #[derive(Clone, Debug)]
+    struct Trap {
+        result: Option<Score>,
+    }
+
+    impl GameState for Trap {
+        type Move = u8;
+
+        fn moves(&self, _is_player: bool) -> Vec<u8> {
+            vec![0, 1]
+        }
+
+        fn apply(&self, player: u8, enemy: u8) -> Self {
+            let result = match (player, enemy) {
+                (0, _) => 0.5,
+                (_, 0) => 1.0,
+                _ => 0.0,
+            };
+            Trap {
+                result: Some(result),
+            }
+        }
+
+        fn is_terminal(&self) -> bool {
+            self.result.is_some()
+        }
+
+        fn reward(&self) -> Score {
+            self.result.unwrap()
+        }
+    }
+
+    /// Matching pennies with a way out. Moves 1 and 2 win when the enemy
+    /// plays the same number and lose otherwise, move 0 is worth 0.4. Without
+    /// knowing the enemy move, mixing 1 and 2 is worth 0.5.
+    #[derive(Clone, Debug)]
+    struct Pennies {
+        result: Option<Score>,
+    }
+
+    impl GameState for Pennies {
+        type Move = u8;
+
+        fn moves(&self, is_player: bool) -> Vec<u8> {
+            if is_player {
+                vec![0, 1, 2]
+            } else {
+                vec![1, 2]
+            }
+        }
+
+        fn apply(&self, player: u8, enemy: u8) -> Self {
+            let result = match player {
+                0 => 0.4,
+                x if x == enemy => 1.0,
+                _ => 0.0,
+            };
+            Pennies {
+                result: Some(result),
+            }
+        }
+
+        fn is_terminal(&self) -> bool {
+            self.result.is_some()
+        }
+
+        fn reward(&self) -> Score {
+            self.result.unwrap()
+        }
+    }
+
+    #[test]
+    fn test_decoupled_does_not_see_player_move() {
+        let mut sequential = MctsSimulation::with_seed(Pennies { result: None }, 1);
+        sequential.run(3000);
+        assert_eq!(sequential.best_move(), Some(0));
+
+        let mut decoupled =
+            MctsSimulation::with_seed(Pennies { result: None }, 1).with_scheme(Scheme::Decoupled);
+        decoupled.run(3000);
+        assert_ne!(decoupled.best_move(), Some(0));
+
+        let root = &decoupled.states[decoupled.root()];
+        assert_eq!(root.enemy_nodes.len(), 2);
+        assert_eq!(root.joint_states.iter().flatten().count(), 6);
+        let enemy_picks: u32 = root
+            .enemy_nodes
+            .iter()
+            .map(|e| decoupled.enemy_nodes[*e].picks)
+            .sum();
+        assert_eq!(enemy_picks, 3000);
+    }
+
+    /// Regret matching and EXP3 converge slower than UCB, but the average
+    /// play goes to the mix of moves 1 and 2.
+    #[test]
+    fn test_bandit_policies() {
+        for name in &["exp3", "rm"] {
+            let mut sim = MctsSimulation::with_seed(Pennies { result: None }, 1)
+                .with_scheme(Scheme::Decoupled)
+                .with_policy(policy::by_name(name).unwrap());
+            sim.run(20000);
+            assert_ne!(sim.best_move(), Some(0), "{}", name);
+            assert!(sim.root_moves()[0].1 < 5000, "{}", name);
+
+            let race = Race {
+                turn: 0,
+                totals: [0, 0],
+            };
+            let mut sim = MctsSimulation::with_seed(race, 1)
+                .with_scheme(Scheme::Decoupled)
+                .with_policy(policy::by_name(name).unwrap());
+            sim.run(5000);
+            assert_eq!(sim.best_move(), Some(3), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_run_for() {
+        let race = Race {
+            turn: 0,
+            totals: [0, 0],
+        };
+        let mut sim = MctsSimulation::with_seed(race, 1);
+        let iterations = sim.run_for(Duration::from_millis(10));
+
+        assert!(iterations >= 8);
+        assert_eq!(sim.iterations(), iterations);
+    }
+
+    #[test]
+    fn test_decoupled_race() {
+        let race = Race {
+            turn: 0,
+            totals: [0, 0],
+        };
+        let mut sim = MctsSimulation::with_seed(race, 1).with_scheme(Scheme::Decoupled);
+        sim.run(3000);
+
+        assert_eq!(sim.best_move(), Some(3));
+    }
+
+    #[test]
+    fn test_race_takes_the_biggest_step() {
+        let mut sim = MctsSimulation::with_seed(
+            Race {
+                turn: 0,
+                totals: [0, 0],
+            },
+            1,
+        );
+        sim.run(3000);
+
+        assert_eq!(sim.iterations(), 3000);
+        assert_eq!(sim.best_move(), Some(3));
+    }
+
+    #[test]
+    fn test_enemy_reply_is_considered() {
+        let mut sim = MctsSimulation::with_seed(Trap { result: None }, 1);
+        sim.run(500);
+
+        assert_eq!(sim.best_move(), Some(0));
+        let picks: u32 = sim.root_moves().iter().map(|m| m.1).sum();
+        assert_eq!(picks, 500);
+    }
+
+    #[test]
+    fn test_terminal_root() {
+        let mut sim = MctsSimulation::with_seed(Trap { result: Some(1.0) }, 1);
+        sim.run(10);
+
+        assert_eq!(sim.best_move(), None);
+        assert_eq!(sim.iterations(), 10);
+        assert_eq!(sim.states_len(), 1);
+    }
+
+    #[test]
+    fn test_spring_game() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "18", "20", "2 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        let mut sim = MctsSimulation::with_seed(SpringState::new(&board, game), 1);
+        sim.run(50);
+
+        let legal = Action::find_next_actions(&game, &board, true);
+        assert!(legal.contains(&sim.best_move().unwrap()));
+        assert_eq!(sim.root_moves().len(), legal.len());
+    }
+
+    #[test]
+    fn test_mixed_leaf_evaluation() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "18", "20", "2 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        let legal = Action::find_next_actions(&game, &board, true);
+        for weight in &[0.5, 1.0] {
+            let mut sim = MctsSimulation::with_seed(SpringState::new(&board, game), 1)
+                .with_playout(Box::new(GreedyPlayout { cutoff: Some(2) }))
+                .with_static_weight(*weight);
+            sim.run(50);
+
+            assert!(legal.contains(&sim.best_move().unwrap()));
+            let score = sim.states[sim.root()].avg_score();
+            assert!(score > 0.0 && score < 1.0, "{}", score);
+        }
     }
 }
@@ -0,0 +1,85 @@
+use std::fmt::Debug;
+
+use crate::engine::{actions::Action, board::Board, game::Game, simulation::Simulation};
+
+use super::nodes::Score;
+
+/// A two player game with simultaneous moves, as seen by the search: both
+/// sides pick a move and the pair is applied at once.
+pub trait GameState: Clone {
+    type Move: Copy + PartialEq + Debug;
+
+    /// Legal moves of one side. Not empty unless the state is terminal.
+    fn moves(&self, is_player: bool) -> Vec<Self::Move>;
+
+    fn apply(&self, player: Self::Move, enemy: Self::Move) -> Self;
+
+    fn is_terminal(&self) -> bool;
+
+    /// Reward of the player in a terminal state, from 0 for a loss to 1 for a
+    /// win. The enemy gets `1 - reward`.
+    fn reward(&self) -> Score;
+
+    /// Guess of the reward in any state, for playouts cut off before the end.
+    fn evaluate(&self) -> Score {
+        if self.is_terminal() {
+            self.reward()
+        } else {
+            0.5
+        }
+    }
+}
+
+/// Lead in heuristic score that `SpringState::evaluate` turns into 0.73.
+const EVALUATION_SCALE: Score = 30.0;
+
+/// The Spring Challenge 2021 game on a given board.
+#[derive(Clone, Copy)]
+pub struct SpringState<'a> {
+    pub board: &'a Board,
+    pub game: Game,
+}
+
+impl<'a> SpringState<'a> {
+    pub fn new(board: &'a Board, game: Game) -> Self {
+        Self { board, game }
+    }
+}
+
+impl<'a> GameState for SpringState<'a> {
+    type Move = Action;
+
+    fn moves(&self, is_player: bool) -> Vec<Action> {
+        Action::find_next_actions(&self.game, self.board, is_player)
+    }
+
+    fn apply(&self, player: Action, enemy: Action) -> Self {
+        Self::new(
+            self.board,
+            self.game.apply_actions(self.board, player, enemy),
+        )
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.game.is_over()
+    }
+
+    fn reward(&self) -> Score {
+        match self.game.final_scores().winner {
+            Some(true) => 1.0,
+            Some(false) => 0.0,
+            None => 0.5,
+        }
+    }
+
+    /// The difference of `Simulation::get_score` of both sides, squashed by
+    /// a sigmoid.
+    fn evaluate(&self) -> Score {
+        if self.is_terminal() {
+            return self.reward();
+        }
+        let player = Simulation::get_score(&self.game, self.board, true).value();
+        let enemy = Simulation::get_score(&self.game, self.board, false).value();
+        1.0 / (1.0 + (-((player - enemy) as Score) / EVALUATION_SCALE).exp())
+    }
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use crate::engine::{actions::Action, board::Board, game::Game, game_loop::Strategy};
+
+use super::{
+    playout, policy,
+    simulation::{MctsSimulation, Scheme},
+    state::SpringState,
+};
+
+/// Decoupled UCT with the selection policy and
+/// playouts of the given names. The tree is built again every turn.
+pub struct DuctStrategy {
+    policy: String,
+    playout: String,
+}
+
+impl DuctStrategy {
+    /// `policy` is one of `policy::POLICY_NAMES`. Plays out randomly.
+    pub fn new(policy: &str) -> DuctStrategy {
+        DuctStrategy {
+            policy: policy.to_owned(),
+            playout: "random".to_owned(),
+        }
+    }
+
+    /// `playout` is one of `playout::PLAYOUT_NAMES`.
+    pub fn with_playout(mut self, playout: &str) -> DuctStrategy {
+        self.playout = playout.to_owned();
+        self
+    }
+}
+
+impl Strategy for DuctStrategy {
+    fn on_start(&mut self, _board: &Board) {}
+
+    fn get_next(&mut self, game: &Game, board: &Board, time_limit: u128) -> Action {
+        let mut sim = MctsSimulation::new(SpringState::new(board, *game))
+            .with_scheme(Scheme::Decoupled)
+            .with_policy(policy::by_name(&self.policy).unwrap())
+            .with_playout(playout::by_name(&self.playout).unwrap());
+        let budget = (time_limit * 3 / 4).max(1) as u64;
+        let iterations = sim.run_for(Duration::from_millis(budget));
+
+        let action = sim
+            .best_move()
+            .filter(|a| a.validate(game, board, true).is_ok())
+            .unwrap_or(Action::WAIT);
+        eprintln!(
+            "duct-{}-{} iterations: {}, states: {}. picked: {}",
+            self.policy,
+            self.playout,
+            iterations,
+            sim.states_len(),
+            action
+        );
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duct_plays_a_legal_action() {
+        let board = Board::default();
+        let game = Game::parse_from_strings(vec![
+            "0", "20", "2 0", "2 0 0", "4", "20 1 0 0", "24 1 0 0", "29 1 1 0", "33 1 1 0",
+        ]);
+        let action = DuctStrategy::new("exp3")
+            .with_playout("greedy")
+            .get_next(&game, &board, 20);
+
+        assert!(action.validate(&game, &board, true).is_ok());
+    }
+}
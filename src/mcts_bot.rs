@@ -0,0 +1,27 @@
+pub mod engine;
+pub mod mcts;
+use engine::{game_loop::*, rules::Rules, strategies::MctsStrategy};
+use mcts::strategy::DuctStrategy;
+use std::env;
+
+/// Plays the full game by default, an optional argument picks another league.
+/// A second argument, one of `mcts::policy::POLICY_NAMES` or `ucb:<c>`, plays
+/// decoupled UCT with that selection policy instead, and a third one of
+/// `mcts::playout::PLAYOUT_NAMES` picks its playouts.
+fn main() {
+    let rules = env::args()
+        .nth(1)
+        .and_then(|x| Rules::by_name(&x))
+        .unwrap_or_default();
+    let settings = GameSettings::new().with_rules(rules);
+    match env::args().nth(2) {
+        Some(policy) if mcts::policy::by_name(&policy).is_some() => {
+            let playout = env::args()
+                .nth(3)
+                .filter(|x| mcts::playout::by_name(x).is_some())
+                .unwrap_or_else(|| "random".to_owned());
+            play_game(DuctStrategy::new(&policy).with_playout(&playout), settings)
+        }
+        _ => play_game(MctsStrategy::new(), settings),
+    }
+}
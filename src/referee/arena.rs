@@ -0,0 +1,229 @@
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::{bot::ProcessBot, map, play_match, MatchResult, MatchSettings};
+
+/// Results of a head-to-head series, always from the first bot's perspective.
+#[derive(Debug, Clone, Default)]
+pub struct ArenaStats {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    pub disqualified: [u32; 2],
+    total_margin: i64,
+    total_points: f64,
+    total_points_squared: f64,
+}
+
+impl ArenaStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    /// Records a match in which the first bot played in `seat`.
+    pub fn add(&mut self, result: &MatchResult, seat: usize) {
+        let points = match result.winner {
+            Some(winner) if winner == seat => {
+                self.wins += 1;
+                1.0
+            }
+            Some(_) => {
+                self.losses += 1;
+                0.0
+            }
+            None => {
+                self.draws += 1;
+                0.5
+            }
+        };
+        if let Some((loser, _)) = result.disqualified {
+            self.disqualified[if loser == seat { 0 } else { 1 }] += 1;
+        }
+        self.total_margin += (result.scores[seat] - result.scores[1 - seat]) as i64;
+        self.total_points += points;
+        self.total_points_squared += points * points;
+    }
+
+    pub fn average_margin(&self) -> f64 {
+        self.total_margin as f64 / self.games() as f64
+    }
+
+    /// Expected points per game: a win is 1, a draw is 1/2.
+    pub fn score(&self) -> f64 {
+        self.total_points / self.games() as f64
+    }
+
+    pub fn elo(&self) -> f64 {
+        elo_difference(self.score())
+    }
+
+    /// Elo bounds of a 95% confidence interval around the observed score.
+    pub fn elo_interval(&self) -> (f64, f64) {
+        let n = self.games() as f64;
+        let score = self.score();
+        let variance = (self.total_points_squared / n - score * score).max(0.0);
+        let error = 1.96 * (variance / n).sqrt();
+        (elo_difference(score - error), elo_difference(score + error))
+    }
+}
+
+pub fn elo_difference(score: f64) -> f64 {
+    if score <= 0.0 {
+        f64::NEG_INFINITY
+    } else if score >= 1.0 {
+        f64::INFINITY
+    } else {
+        -400.0 * (1.0 / score - 1.0).log10()
+    }
+}
+
+impl Display for ArenaStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (low, high) = self.elo_interval();
+        write!(
+            f,
+            "games: {}, wins: {}, losses: {}, draws: {}, disqualified: {}/{}, margin: {:.2}, elo: {:.1} [{:.1}, {:.1}]",
+            self.games(),
+            self.wins,
+            self.losses,
+            self.draws,
+            self.disqualified[0],
+            self.disqualified[1],
+            self.average_margin(),
+            self.elo(),
+            low,
+            high
+        )
+    }
+}
+
+pub struct ArenaSettings {
+    pub commands: [String; 2],
+    pub games: usize,
+    pub threads: usize,
+    pub seed: u64,
+    pub match_settings: MatchSettings,
+}
+
+/// Plays a single arena game. Games come in pairs on the same map, and the
+/// first bot takes the second seat in every odd game.
+fn play_arena_game(settings: &ArenaSettings, game_id: usize) -> (MatchResult, usize) {
+    let seat = game_id % 2;
+    let mut rng = StdRng::seed_from_u64(settings.seed.wrapping_add((game_id / 2) as u64));
+    let (board, game) = map::generate(&mut rng, settings.match_settings.rules);
+
+    let mut bots = [0, 1].iter().map(|i| {
+        let command = &settings.commands[(i + seat) % 2];
+        ProcessBot::spawn(command, false)
+            .unwrap_or_else(|e| panic!("failed to start {:?}: {}", command, e))
+    });
+    let mut first = bots.next().unwrap();
+    let mut second = bots.next().unwrap();
+
+    let result = play_match(
+        &board,
+        game,
+        [&mut first, &mut second],
+        &settings.match_settings,
+    );
+    (result, seat)
+}
+
+/// Runs the whole series on a pool of worker threads. `on_result` is called on
+/// the current thread after each game with the updated totals.
+pub fn run_arena<F>(settings: ArenaSettings, mut on_result: F) -> ArenaStats
+where
+    F: FnMut(usize, &MatchResult, usize, &ArenaStats),
+{
+    let settings = Arc::new(settings);
+    let next_game = Arc::new(AtomicUsize::new(0));
+    let (sender, results) = mpsc::channel();
+
+    let workers: Vec<_> = (0..settings.threads.max(1))
+        .map(|_| {
+            let settings = Arc::clone(&settings);
+            let next_game = Arc::clone(&next_game);
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                let game_id = next_game.fetch_add(1, Ordering::SeqCst);
+                if game_id >= settings.games {
+                    break;
+                }
+                let (result, seat) = play_arena_game(&settings, game_id);
+                if sender.send((game_id, result, seat)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let mut stats = ArenaStats::new();
+    for (game_id, result, seat) in results {
+        stats.add(&result, seat);
+        on_result(game_id, &result, seat, &stats);
+    }
+    for worker in workers {
+        worker.join().expect("arena worker panicked");
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(scores: [i32; 2], winner: Option<usize>) -> MatchResult {
+        MatchResult {
+            scores,
+            winner,
+            day: 24,
+            turns: 100,
+            disqualified: None,
+        }
+    }
+
+    #[test]
+    fn test_elo_difference() {
+        assert_eq!(elo_difference(0.5), 0.0);
+        assert!((elo_difference(0.75) - 190.85).abs() < 0.01);
+        assert!((elo_difference(0.25) + 190.85).abs() < 0.01);
+        assert_eq!(elo_difference(1.0), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_stats_are_from_first_bot_perspective() {
+        let mut stats = ArenaStats::new();
+        stats.add(&result([80, 70], Some(0)), 0);
+        stats.add(&result([80, 70], Some(0)), 1);
+        stats.add(&result([60, 90], Some(1)), 1);
+        stats.add(&result([75, 75], None), 0);
+
+        assert_eq!((stats.wins, stats.losses, stats.draws), (2, 1, 1));
+        assert_eq!(stats.average_margin(), (10.0 - 10.0 + 30.0 + 0.0) / 4.0);
+        assert_eq!(stats.score(), 2.5 / 4.0);
+    }
+
+    #[test]
+    fn test_elo_interval_contains_elo() {
+        let mut stats = ArenaStats::new();
+        for i in 0..20 {
+            stats.add(&result([1, 0], Some(if i % 3 == 0 { 1 } else { 0 })), 0);
+        }
+        let (low, high) = stats.elo_interval();
+
+        assert!(low < stats.elo() && stats.elo() < high);
+    }
+}
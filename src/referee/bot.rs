@@ -0,0 +1,146 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use itertools::Itertools;
+
+use crate::engine::{actions::Action, board::Board, game::Game};
+
+use super::{Seat, SeatError};
+
+/// Reads an action from a line of bot output. Anything after the action
+/// parameters is a message for the viewer and is ignored.
+pub fn parse_output(line: &str) -> Result<Action, SeatError> {
+    let words = line.split_whitespace().collect_vec();
+    let arity = match words.first() {
+        Some(&"WAIT") => 1,
+        Some(&"GROW") | Some(&"COMPLETE") => 2,
+        Some(&"SEED") => 3,
+        _ => return Err(SeatError::InvalidOutput(line.to_owned())),
+    };
+    if words.len() < arity {
+        return Err(SeatError::InvalidOutput(line.to_owned()));
+    }
+    words[..arity]
+        .join(" ")
+        .parse::<Action>()
+        .map_err(|_| SeatError::InvalidOutput(line.to_owned()))
+}
+
+/// A bot running as a child process, talking the CodinGame stdin/stdout protocol.
+pub struct ProcessBot {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl ProcessBot {
+    pub fn spawn(command: &str, show_stderr: bool) -> io::Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty bot command"))?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(if show_stderr {
+                Stdio::inherit()
+            } else {
+                Stdio::null()
+            })
+            .spawn()?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line.map(|l| sender.send(l)) {
+                    Ok(Ok(())) => {}
+                    _ => break,
+                }
+            }
+        });
+
+        Ok(Self {
+            name: command.to_owned(),
+            child,
+            stdin,
+            lines,
+        })
+    }
+
+    fn send(&mut self, lines: &[String]) -> Result<(), SeatError> {
+        for line in lines {
+            writeln!(self.stdin, "{}", line).map_err(|_| SeatError::Crashed)?;
+        }
+        self.stdin.flush().map_err(|_| SeatError::Crashed)
+    }
+
+    fn receive(&mut self, time_limit: Duration) -> Result<String, SeatError> {
+        self.lines.recv_timeout(time_limit).map_err(|e| match e {
+            RecvTimeoutError::Timeout => SeatError::Timeout,
+            RecvTimeoutError::Disconnected => SeatError::Crashed,
+        })
+    }
+}
+
+impl Seat for ProcessBot {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn start(&mut self, board: &Board) -> Result<(), SeatError> {
+        let mut lines = vec![board.cells().count().to_string()];
+        lines.extend(board.cells().map(|c| c.to_string()));
+        self.send(&lines)
+    }
+
+    fn next_action(
+        &mut self,
+        game: &Game,
+        _board: &Board,
+        moves: &[Action],
+        time_limit: Duration,
+    ) -> Result<Action, SeatError> {
+        let mut lines = game.to_strings();
+        lines.push(moves.len().to_string());
+        lines.extend(moves.iter().map(|m| m.to_string()));
+        self.send(&lines)?;
+        parse_output(&self.receive(time_limit)?)
+    }
+}
+
+impl Drop for ProcessBot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_output() {
+        assert_eq!(parse_output("WAIT"), Ok(Action::WAIT));
+        assert_eq!(parse_output("WAIT sleeping"), Ok(Action::WAIT));
+        assert_eq!(parse_output("GROW 12 go go"), Ok(Action::GROW(12)));
+        assert_eq!(parse_output("SEED 1 7"), Ok(Action::SEED(1, 7)));
+        assert_eq!(
+            parse_output("SEED 1"),
+            Err(SeatError::InvalidOutput("SEED 1".to_owned()))
+        );
+        assert_eq!(
+            parse_output("JUMP"),
+            Err(SeatError::InvalidOutput("JUMP".to_owned()))
+        );
+    }
+}
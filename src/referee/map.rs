@@ -0,0 +1,119 @@
+use itertools::Itertools;
+use rand::{prelude::SliceRandom, Rng};
+
+use crate::engine::{
+    board::{coord_to_index, index_to_coord, Board, CubeCoord},
+    game::Game,
+    rules::Rules,
+    tree::Tree,
+};
+
+pub const MAX_EMPTY_CELLS: usize = 10;
+pub const STARTING_TREE_COUNT: usize = 2;
+pub const STARTING_TREE_DISTANCE: u8 = 2;
+
+fn opposite(index: u8) -> u8 {
+    let c = index_to_coord(index);
+    coord_to_index(CubeCoord::new(-c.x, -c.y, -c.z))
+}
+
+fn generate_holes<R: Rng>(rng: &mut R) -> Vec<u8> {
+    let wanted = rng.gen_range(0..MAX_EMPTY_CELLS + 1);
+    let mut holes = Vec::new();
+    while holes.len() + 1 < wanted {
+        let index = rng.gen_range(0..37u8);
+        if holes.contains(&index) {
+            continue;
+        }
+        holes.push(index);
+        if opposite(index) != index {
+            holes.push(opposite(index));
+        }
+    }
+    holes
+}
+
+fn place_starting_trees<R: Rng>(rng: &mut R, board: &Board, size: u8) -> Option<Vec<Tree>> {
+    let center = CubeCoord::new(0, 0, 0);
+    let mut available = board
+        .cells()
+        .filter(|c| c.richness > 0 && index_to_coord(c.index).distance_to(center) == 3)
+        .map(|c| c.index)
+        .collect_vec();
+
+    let mut trees = Vec::new();
+    for _ in 0..STARTING_TREE_COUNT {
+        let index = *available.choose(rng)?;
+        let (normal, opposite) = (index_to_coord(index), index_to_coord(opposite(index)));
+        available.retain(|i| {
+            let c = index_to_coord(*i);
+            c.distance_to(normal) > STARTING_TREE_DISTANCE
+                && c.distance_to(opposite) > STARTING_TREE_DISTANCE
+        });
+        trees.push(Tree::new(index, size, true, false));
+        trees.push(Tree::new(coord_to_index(opposite), size, false, false));
+    }
+    Some(trees)
+}
+
+/// Generates a random Spring 2021 map: a symmetric set of unusable cells and
+/// mirrored starting trees on the outer ring. The returned game is the day 0
+/// state seen by the first player, after the initial sun collection.
+pub fn generate<R: Rng>(rng: &mut R, rules: Rules) -> (Board, Game) {
+    loop {
+        let board = Board::default_with_inactive(generate_holes(rng).into_iter());
+        if let Some(trees) = place_starting_trees(rng, &board, rules.starting_tree_size) {
+            let game = Game::new(
+                trees.into_iter().collect(),
+                rules.starting_nutrients,
+                0,
+                0,
+                0,
+                0,
+                0,
+                false,
+            )
+            .with_rules(rules);
+            let game = game.start_of_day(&board);
+            return (board, game);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_opposite() {
+        assert_eq!(opposite(0), 0);
+        assert_eq!(opposite(1), 4);
+        assert_eq!(opposite(19), 28);
+        assert_eq!(opposite(36), 27);
+    }
+
+    #[test]
+    fn test_generated_map_is_symmetric() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..100 {
+            let (board, game) = generate(&mut rng, Rules::default());
+            for cell in board.cells() {
+                assert_eq!(
+                    cell.richness == 0,
+                    board.get_richness(opposite(cell.index)) == 0
+                );
+            }
+
+            assert_eq!(game.trees().len(true), STARTING_TREE_COUNT as u8);
+            assert_eq!(game.trees().len(false), STARTING_TREE_COUNT as u8);
+            for tree in game.trees().iter_trees_for(true) {
+                let mirrored = game.trees().get(opposite(tree.index()));
+                assert!(!mirrored.is_mine());
+                assert!(board.get_richness(tree.index()) > 0);
+            }
+            assert_eq!(game.get_sun_points(true), game.get_sun_points(false));
+        }
+    }
+}
@@ -0,0 +1,349 @@
+pub mod arena;
+pub mod bot;
+pub mod map;
+pub mod tournament;
+
+use std::{fmt::Display, time::Duration};
+
+use crate::engine::{actions::Action, board::Board, game::Game, rules::Rules};
+
+pub use crate::engine::game::MAX_DAYS;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SeatError {
+    Timeout,
+    Crashed,
+    InvalidOutput(String),
+}
+
+impl Display for SeatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeatError::Timeout => write!(f, "timeout"),
+            SeatError::Crashed => write!(f, "crashed"),
+            SeatError::InvalidOutput(line) => write!(f, "invalid output: {:?}", line),
+        }
+    }
+}
+
+/// A participant of a match. The game passed to `next_action` is always seen
+/// from the seat's own perspective, exactly as the protocol describes it.
+pub trait Seat {
+    fn name(&self) -> &str;
+
+    fn start(&mut self, board: &Board) -> Result<(), SeatError>;
+
+    fn next_action(
+        &mut self,
+        game: &Game,
+        board: &Board,
+        moves: &[Action],
+        time_limit: Duration,
+    ) -> Result<Action, SeatError>;
+}
+
+pub struct MatchSettings {
+    pub first_turn_timeout: Duration,
+    pub turn_timeout: Duration,
+    pub verbose: bool,
+    pub rules: Rules,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MatchSettings {
+    /// The official limits are 1000 ms for the first turn and 100 ms after it.
+    /// The default leaves a margin for process scheduling on a busy machine.
+    pub fn new() -> Self {
+        Self {
+            first_turn_timeout: Duration::from_millis(1050),
+            turn_timeout: Duration::from_millis(150),
+            verbose: false,
+            rules: Rules::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchResult {
+    pub scores: [i32; 2],
+    pub winner: Option<usize>,
+    pub day: u8,
+    pub turns: u32,
+    pub disqualified: Option<(usize, SeatError)>,
+}
+
+impl MatchResult {
+    fn finished(game: &Game, turns: u32) -> Self {
+        let result = game.final_scores();
+        Self {
+            scores: [result.player_score as i32, result.enemy_score as i32],
+            winner: result.winner.map(|is_player| if is_player { 0 } else { 1 }),
+            day: game.day,
+            turns,
+            disqualified: None,
+        }
+    }
+
+    fn disqualified(game: &Game, turns: u32, seat: usize, error: SeatError) -> Self {
+        let mut scores = [game.get_points(true) as i32, game.get_points(false) as i32];
+        scores[seat] = -1;
+        Self {
+            scores,
+            winner: Some(1 - seat),
+            day: game.day,
+            turns,
+            disqualified: Some((seat, error)),
+        }
+    }
+}
+
+impl Display for MatchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "player 1: {}, player 2: {}, ",
+            self.scores[0], self.scores[1]
+        )?;
+        match self.winner {
+            Some(seat) => write!(f, "winner: player {}", seat + 1)?,
+            None => write!(f, "draw")?,
+        }
+        if let Some((seat, ref error)) = self.disqualified {
+            write!(f, " (player {} {})", seat + 1, error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the game as the given seat sees it: seat 0 is the "player" of the
+/// referee's game, seat 1 sees both sides swapped.
+fn view_for(game: &Game, seat: usize) -> Game {
+    if seat == 0 {
+        *game
+    } else {
+        game.swap_players()
+    }
+}
+
+/// Plays a full game between two seats. Every turn each awake seat receives
+/// its view of the game and answers with an action. An illegal action is
+/// replaced with WAIT, while a timeout, a crash or unreadable output
+/// disqualifies the seat.
+pub fn play_match(
+    board: &Board,
+    game: Game,
+    mut seats: [&mut dyn Seat; 2],
+    settings: &MatchSettings,
+) -> MatchResult {
+    let mut game = game;
+    let mut answers = [0u32, 0u32];
+    let mut turns = 0;
+
+    for (i, seat) in seats.iter_mut().enumerate() {
+        if let Err(e) = seat.start(board) {
+            return MatchResult::disqualified(&game, turns, i, e);
+        }
+    }
+
+    while !game.is_over() {
+        let mut actions = [Action::WAIT, Action::WAIT];
+        for i in 0..2 {
+            if game.is_waiting(i == 0) {
+                continue;
+            }
+            let view = view_for(&game, i);
+            let moves = Action::find_next_actions(&view, board, true);
+            let time_limit = if answers[i] == 0 {
+                settings.first_turn_timeout
+            } else {
+                settings.turn_timeout
+            };
+            answers[i] += 1;
+
+            match seats[i].next_action(&view, board, &moves, time_limit) {
+                Ok(action) => match action.validate(&view, board, true) {
+                    Ok(()) => actions[i] = action,
+                    Err(reason) => {
+                        if settings.verbose {
+                            eprintln!(
+                                "day {}: player {} illegal {}: {}",
+                                game.day,
+                                i + 1,
+                                action,
+                                reason
+                            );
+                        }
+                    }
+                },
+                Err(e) => return MatchResult::disqualified(&game, turns, i, e),
+            }
+        }
+
+        if settings.verbose {
+            eprintln!(
+                "day {}: player 1 {}, player 2 {}",
+                game.day, actions[0], actions[1]
+            );
+        }
+
+        game = game.apply_actions(board, actions[0], actions[1]);
+        turns += 1;
+    }
+
+    MatchResult::finished(&game, turns)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    struct FirstMoveSeat {
+        name: String,
+        last_move: usize,
+    }
+
+    impl FirstMoveSeat {
+        fn new(last_move: usize) -> Self {
+            Self {
+                name: format!("first move of {}", last_move),
+                last_move,
+            }
+        }
+    }
+
+    impl Seat for FirstMoveSeat {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn start(&mut self, _board: &Board) -> Result<(), SeatError> {
+            Ok(())
+        }
+
+        fn next_action(
+            &mut self,
+            _game: &Game,
+            _board: &Board,
+            moves: &[Action],
+            _time_limit: Duration,
+        ) -> Result<Action, SeatError> {
+            Ok(moves[std::cmp::min(self.last_move, moves.len() - 1)])
+        }
+    }
+
+    struct CrashingSeat {}
+
+    impl Seat for CrashingSeat {
+        fn name(&self) -> &str {
+            "crashing"
+        }
+
+        fn start(&mut self, _board: &Board) -> Result<(), SeatError> {
+            Ok(())
+        }
+
+        fn next_action(
+            &mut self,
+            _game: &Game,
+            _board: &Board,
+            _moves: &[Action],
+            _time_limit: Duration,
+        ) -> Result<Action, SeatError> {
+            Err(SeatError::Crashed)
+        }
+    }
+
+    #[test]
+    fn test_view_for_second_seat() {
+        let game =
+            Game::parse_from_strings(vec!["3", "20", "4 1", "3 2 1", "2", "3 0 0 1", "17 1 1 0"]);
+        let view = view_for(&game, 1);
+
+        assert_eq!(
+            view.to_strings(),
+            vec!["3", "20", "3 2", "4 1 0", "2", "3 0 1 1", "17 1 0 0"]
+        );
+    }
+
+    #[test]
+    fn test_waiting_seats_play_all_days() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let (board, game) = map::generate(&mut rng, Rules::default());
+        let mut first = FirstMoveSeat::new(0);
+        let mut second = FirstMoveSeat::new(0);
+
+        let result = play_match(
+            &board,
+            game,
+            [&mut first, &mut second],
+            &MatchSettings::new(),
+        );
+
+        assert_eq!(result.day, MAX_DAYS);
+        assert_eq!(result.turns, MAX_DAYS as u32);
+        assert_eq!(result.winner, None);
+        assert_eq!(result.scores[0], result.scores[1]);
+    }
+
+    #[test]
+    fn test_active_seat_wins_against_waiting_seat() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let (board, game) = map::generate(&mut rng, Rules::default());
+        let mut waiting = FirstMoveSeat::new(0);
+        let mut active = FirstMoveSeat::new(1);
+
+        let result = play_match(
+            &board,
+            game,
+            [&mut waiting, &mut active],
+            &MatchSettings::new(),
+        );
+
+        assert_eq!(result.day, MAX_DAYS);
+        assert_eq!(result.winner, Some(1));
+    }
+
+    #[test]
+    fn test_wood_2_lasts_one_day() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let (board, game) = map::generate(&mut rng, Rules::wood_2());
+        let mut waiting = FirstMoveSeat::new(0);
+        let mut completing = FirstMoveSeat::new(1);
+
+        let result = play_match(
+            &board,
+            game,
+            [&mut waiting, &mut completing],
+            &MatchSettings::new(),
+        );
+
+        assert_eq!(result.day, 1);
+        assert_eq!(result.winner, Some(1));
+    }
+
+    #[test]
+    fn test_crash_disqualifies() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let (board, game) = map::generate(&mut rng, Rules::default());
+        let mut crashing = CrashingSeat {};
+        let mut waiting = FirstMoveSeat::new(0);
+
+        let result = play_match(
+            &board,
+            game,
+            [&mut waiting, &mut crashing],
+            &MatchSettings::new(),
+        );
+
+        assert_eq!(result.winner, Some(0));
+        assert_eq!(result.scores[1], -1);
+        assert_eq!(result.disqualified, Some((1, SeatError::Crashed)));
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    fmt::Display,
+    panic::{self, AssertUnwindSafe},
+    time::Duration,
+};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::engine::{actions::Action, board::Board, game::Game, game_loop::Strategy};
+
+use super::{map, play_match, MatchSettings, Seat, SeatError};
+
+/// Runs a `Strategy` in the referee's process. A panic inside the strategy
+/// is reported the same way as a crashed bot process.
+pub struct StrategySeat {
+    name: String,
+    strategy: Box<dyn Strategy>,
+}
+
+impl StrategySeat {
+    pub fn new(name: &str, strategy: Box<dyn Strategy>) -> Self {
+        Self {
+            name: name.to_owned(),
+            strategy,
+        }
+    }
+}
+
+impl Seat for StrategySeat {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn start(&mut self, board: &Board) -> Result<(), SeatError> {
+        let strategy = &mut self.strategy;
+        panic::catch_unwind(AssertUnwindSafe(|| strategy.on_start(board)))
+            .map_err(|_| SeatError::Crashed)
+    }
+
+    fn next_action(
+        &mut self,
+        game: &Game,
+        board: &Board,
+        _moves: &[Action],
+        time_limit: Duration,
+    ) -> Result<Action, SeatError> {
+        let strategy = &mut self.strategy;
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            strategy.get_next(game, board, time_limit.as_millis())
+        }))
+        .map_err(|_| SeatError::Crashed)
+    }
+}
+
+/// Round-robin results. `points[i][j]` is what entry `i` scored against
+/// entry `j`, counting a win as 1 and a draw as 1/2.
+pub struct TournamentTable {
+    pub names: Vec<String>,
+    pub points: Vec<Vec<f64>>,
+    pub games: Vec<Vec<u32>>,
+}
+
+impl TournamentTable {
+    fn new(names: Vec<String>) -> Self {
+        let n = names.len();
+        Self {
+            names,
+            points: vec![vec![0.0; n]; n],
+            games: vec![vec![0; n]; n],
+        }
+    }
+
+    fn add(&mut self, first: usize, second: usize, winner: Option<usize>) {
+        let first_points = match winner {
+            Some(0) => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        };
+        self.points[first][second] += first_points;
+        self.points[second][first] += 1.0 - first_points;
+        self.games[first][second] += 1;
+        self.games[second][first] += 1;
+    }
+
+    pub fn total_points(&self, entry: usize) -> f64 {
+        self.points[entry].iter().sum()
+    }
+}
+
+impl Display for TournamentTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self.names.iter().map(|n| n.len()).max().unwrap_or(0).max(6);
+        write!(f, "{:width$}", "", width = width)?;
+        for name in &self.names {
+            write!(f, " {:>width$}", name, width = width)?;
+        }
+        writeln!(f, " {:>width$}", "total", width = width)?;
+
+        for (i, name) in self.names.iter().enumerate() {
+            write!(f, "{:width$}", name, width = width)?;
+            for j in 0..self.names.len() {
+                if i == j {
+                    write!(f, " {:>width$}", "-", width = width)?;
+                } else {
+                    let cell = format!("{}/{}", self.points[i][j], self.games[i][j]);
+                    write!(f, " {:>width$}", cell, width = width)?;
+                }
+            }
+            writeln!(f, " {:>width$}", self.total_points(i), width = width)?;
+        }
+        Ok(())
+    }
+}
+
+/// Plays every pair of strategies against each other `games_per_pair` times,
+/// on the same kind of paired maps as the arena: each map is played twice
+/// with the seats swapped.
+pub fn run_tournament<F>(
+    names: &[&str],
+    create: F,
+    games_per_pair: usize,
+    seed: u64,
+    settings: &MatchSettings,
+) -> TournamentTable
+where
+    F: Fn(&str) -> Box<dyn Strategy>,
+{
+    let mut table = TournamentTable::new(names.iter().map(|x| x.to_string()).collect());
+
+    for i in 0..names.len() {
+        for j in i + 1..names.len() {
+            for game_id in 0..games_per_pair {
+                let mut rng = StdRng::seed_from_u64(seed.wrapping_add((game_id / 2) as u64));
+                let (board, game) = map::generate(&mut rng, settings.rules);
+                let (first, second) = if game_id % 2 == 0 { (i, j) } else { (j, i) };
+                let mut first_seat = StrategySeat::new(names[first], create(names[first]));
+                let mut second_seat = StrategySeat::new(names[second], create(names[second]));
+
+                let result =
+                    play_match(&board, game, [&mut first_seat, &mut second_seat], settings);
+                table.add(first, second, result.winner);
+            }
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{
+        rules::Rules,
+        strategies::{by_name, MctsStrategy, RandomStrategy},
+    };
+
+    use super::*;
+
+    struct PanickingStrategy {}
+
+    impl Strategy for PanickingStrategy {
+        fn on_start(&mut self, _board: &Board) {}
+
+        fn get_next(&mut self, _game: &Game, _board: &Board, _time_left: u128) -> Action {
+            panic!("no moves for you")
+        }
+    }
+
+    #[test]
+    fn test_panic_is_a_crash() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let (board, game) = map::generate(&mut rng, Rules::default());
+        let mut seat = StrategySeat::new("panicking", Box::new(PanickingStrategy {}));
+
+        let result = seat.next_action(&game, &board, &[], Duration::from_millis(100));
+
+        assert_eq!(result, Err(SeatError::Crashed));
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let names = ["random", "wood", "random"];
+        let table = run_tournament(
+            &names,
+            |name| by_name(name).unwrap_or_else(|| Box::new(RandomStrategy::new())),
+            4,
+            1,
+            &MatchSettings::new(),
+        );
+
+        for i in 0..names.len() {
+            assert_eq!(table.games[i].iter().sum::<u32>(), 8);
+        }
+        let total: f64 = (0..names.len()).map(|i| table.total_points(i)).sum();
+        assert_eq!(total, 12.0);
+    }
+
+    #[test]
+    fn test_mcts_plays_a_full_game() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let rules = Rules::wood_1();
+        let (board, game) = map::generate(&mut rng, rules);
+        let mut settings = MatchSettings::new();
+        settings.rules = rules;
+        settings.turn_timeout = Duration::from_millis(40);
+
+        let mut mcts = StrategySeat::new("mcts", Box::new(MctsStrategy::new()));
+        let mut random = StrategySeat::new("random", Box::new(RandomStrategy::new()));
+        let result = play_match(&board, game, [&mut mcts, &mut random], &settings);
+
+        assert_eq!(result.disqualified, None);
+        assert_eq!(result.day, rules.max_days);
+    }
+}
@@ -0,0 +1,112 @@
+pub mod engine;
+pub mod referee;
+
+use std::{
+    env, process,
+    time::{Duration, Instant},
+};
+
+use engine::{
+    board::Board,
+    game::Game,
+    perft::{perft, perft_divide},
+    rules::{Rules, LEAGUE_NAMES},
+};
+use rand::{rngs::StdRng, SeedableRng};
+use referee::{bot::ProcessBot, map, play_match, MatchSettings};
+
+const USAGE: &str = "usage: referee <player 1 command> <player 2 command> [--seed <n>] [--timeout-ms <n>] [--league <name>] [--verbose] [--show-stderr]
+       referee perft <depth> [--divide] [--seed <n>] [--league <name>]";
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    eprintln!("leagues: {}", LEAGUE_NAMES.join(", "));
+    process::exit(2);
+}
+
+/// Counts the game tree of the generated map for every depth up to `depth`.
+fn run_perft(board: &Board, game: &Game, depth: u32, divide: bool) {
+    for d in 1..depth + 1 {
+        let start = Instant::now();
+        let count = perft(game, board, d);
+        println!(
+            "perft {}: {} ({} ms)",
+            d,
+            count,
+            start.elapsed().as_millis()
+        );
+    }
+    if divide {
+        for ((player, enemy), count) in perft_divide(game, board, depth) {
+            println!("{} / {}: {}", player, enemy, count);
+        }
+    }
+}
+
+fn main() {
+    let mut commands = Vec::new();
+    let mut perft_depth = None;
+    let mut divide = false;
+    let mut seed: u64 = rand::random();
+    let mut settings = MatchSettings::new();
+    let mut show_stderr = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "perft" if commands.is_empty() && perft_depth.is_none() => {
+                perft_depth = Some(
+                    args.next()
+                        .and_then(|x| x.parse().ok())
+                        .unwrap_or_else(|| exit_with_usage()),
+                )
+            }
+            "--divide" => divide = true,
+            "--seed" => {
+                seed = args
+                    .next()
+                    .and_then(|x| x.parse().ok())
+                    .unwrap_or_else(|| exit_with_usage())
+            }
+            "--timeout-ms" => {
+                settings.turn_timeout = args
+                    .next()
+                    .and_then(|x| x.parse().ok())
+                    .map(Duration::from_millis)
+                    .unwrap_or_else(|| exit_with_usage())
+            }
+            "--league" => {
+                settings.rules = args
+                    .next()
+                    .and_then(|x| Rules::by_name(&x))
+                    .unwrap_or_else(|| exit_with_usage())
+            }
+            "--verbose" => settings.verbose = true,
+            "--show-stderr" => show_stderr = true,
+            _ => commands.push(arg),
+        }
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (board, game) = map::generate(&mut rng, settings.rules);
+    if let Some(depth) = perft_depth {
+        println!("seed: {}", seed);
+        run_perft(&board, &game, depth, divide);
+        return;
+    }
+    if commands.len() != 2 {
+        exit_with_usage();
+    }
+    let mut bots = commands.iter().map(|c| {
+        ProcessBot::spawn(c, show_stderr).unwrap_or_else(|e| {
+            eprintln!("failed to start {:?}: {}", c, e);
+            process::exit(1)
+        })
+    });
+    let mut first = bots.next().unwrap();
+    let mut second = bots.next().unwrap();
+
+    let result = play_match(&board, game, [&mut first, &mut second], &settings);
+    println!("seed: {}", seed);
+    println!("{}", result);
+}
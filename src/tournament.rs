@@ -0,0 +1,70 @@
+pub mod engine;
+pub mod referee;
+
+use std::{env, process, time::Duration};
+
+use engine::{
+    rules::{Rules, LEAGUE_NAMES},
+    strategies::{by_name, STRATEGY_NAMES},
+};
+use referee::{tournament::run_tournament, MatchSettings};
+
+const USAGE: &str = "usage: tournament [<strategy>...] [-n <games per pair>] [--seed <n>] [--time-ms <n>] [--league <name>]";
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    eprintln!("strategies: {}", STRATEGY_NAMES.join(", "));
+    eprintln!("leagues: {}", LEAGUE_NAMES.join(", "));
+    process::exit(2);
+}
+
+fn next_number<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>) -> T {
+    args.next()
+        .and_then(|x| x.parse().ok())
+        .unwrap_or_else(|| exit_with_usage())
+}
+
+fn main() {
+    let mut names = Vec::new();
+    let mut games = 10;
+    let mut seed: u64 = rand::random();
+    let mut settings = MatchSettings::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" => games = next_number(&mut args),
+            "--seed" => seed = next_number(&mut args),
+            "--time-ms" => {
+                let time = Duration::from_millis(next_number(&mut args));
+                settings.first_turn_timeout = time;
+                settings.turn_timeout = time;
+            }
+            "--league" => {
+                settings.rules = args
+                    .next()
+                    .and_then(|x| Rules::by_name(&x))
+                    .unwrap_or_else(|| exit_with_usage())
+            }
+            name if by_name(name).is_some() => names.push(arg),
+            _ => exit_with_usage(),
+        }
+    }
+    if names.is_empty() {
+        names = STRATEGY_NAMES.iter().map(|x| x.to_string()).collect();
+    }
+    if names.len() < 2 {
+        exit_with_usage();
+    }
+
+    println!("seed: {}", seed);
+    let names: Vec<&str> = names.iter().map(|x| x.as_str()).collect();
+    let table = run_tournament(
+        &names,
+        |name| by_name(name).unwrap(),
+        games,
+        seed,
+        &settings,
+    );
+    print!("{}", table);
+}
@@ -1,2 +1,2 @@
 cargo build --release --bins
-java -jar tests/brutalizer.jar -r "java -jar tests/spring.jar" -p1 "target/release/beam" -p2 "target/release/beam" -t 8 -n 100 -l "./dist/logs/"
\ No newline at end of file
+target/release/arena "target/release/beam" "target/release/beam" -t 8 -n 100